@@ -1,22 +1,43 @@
-use crate::zip_command::ZipCommand;
-use fs_extra::dir::{CopyOptions as DirCopyOptions, copy as copy_dir};
+use crate::archiver::{ArchiveOptions, Archiver};
+use crate::compression::CompressionPolicy;
+use crate::error::ArchiveError;
+use crate::exclude::ExcludeRules;
+use crate::report::StepProgress;
 use fs_extra::file::{CopyOptions as FileCopyOptions, copy as copy_file};
-use std::fs::{self, Metadata};
+use std::fs::{self, File, Metadata};
 use std::io;
 use std::path::{Path, PathBuf};
 use tempfile::TempDir;
+use walkdir::WalkDir;
 
 pub struct ArchiveFiles {
     pub files: Vec<ArchiveFile>,
+
+    /// 压缩策略与进度, 排除规则由各 `ArchiveFile` 在拷贝时应用
+    options: ArchiveOptions,
 }
 
 impl ArchiveFiles {
     pub fn new(files: Vec<ArchiveFile>) -> Result<Self, ArchiveError> {
-        Ok(ArchiveFiles { files })
+        Ok(ArchiveFiles {
+            files,
+            options: ArchiveOptions::default(),
+        })
     }
+
+    pub fn compression(mut self, compression: CompressionPolicy) -> Self {
+        self.options.compression = compression;
+        self
+    }
+
+    pub fn progress(mut self, progress: StepProgress) -> Self {
+        self.options.progress = Some(progress);
+        self
+    }
+
     pub fn zip_with<P: AsRef<Path>>(
         &self,
-        zip_command: &ZipCommand,
+        archiver: &dyn Archiver,
         to: P,
     ) -> Result<(), ArchiveError> {
         let tmp_dir = TempDir::new()?;
@@ -27,27 +48,59 @@ impl ArchiveFiles {
             file.copy_to(staging_root)?
         }
 
-        zip_command.zip_dir(to.as_ref(), staging_root)?;
+        archiver.zip_dir(to.as_ref(), staging_root, &self.options)?;
 
         Ok(())
     }
 
+    /// 压缩到 `to`, 写入过程见 [`zip_atomically`]
     pub fn zip_and_rename<P: AsRef<Path>>(
         &self,
-        zip_command: &ZipCommand,
+        archiver: &dyn Archiver,
         to: P,
     ) -> Result<(), ArchiveError> {
-        let zip_to = to.as_ref().with_extension("zip");
-        self.zip_with(zip_command, &zip_to)?;
+        zip_atomically(to.as_ref(), |zip_to| self.zip_with(archiver, zip_to))
+    }
+}
 
-        std::fs::rename(&zip_to, &to).inspect_err(|_| {
-            let _ = std::fs::remove_file(&zip_to);
-        })?;
+/// 先压缩到 `to` 所在目录下新建的临时目录中, 同步到磁盘后再原子地重命名为 `to`
+///
+/// 每次都写入新文件, 外部 zip 程序不会向残留的同名 .zip 追加条目;
+/// 中途失败时临时目录随之删除, `to` 保持原样。
+fn zip_atomically(
+    to: &Path,
+    zip: impl FnOnce(&Path) -> Result<(), ArchiveError>,
+) -> Result<(), ArchiveError> {
+    let dir = match to.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let staging = tempfile::Builder::new()
+        .prefix(".vlp-")
+        .tempdir_in(dir)
+        .map_err(|e| ArchiveError::io_at(dir, e))?;
+    // 外部 zip 程序会为没有扩展名的文件名追加 .zip
+    let zip_to = staging.path().join("output.zip");
+    zip(&zip_to)?;
 
-        Ok(())
-    }
+    File::open(&zip_to)
+        .and_then(|file| file.sync_all())
+        .map_err(|e| ArchiveError::io_at(&zip_to, e))?;
+    fs::rename(&zip_to, to).map_err(|e| ArchiveError::io_at(to, e))?;
+    sync_dir(dir);
+
+    Ok(())
+}
+
+/// 将目录项的修改（如重命名）写入磁盘, 失败时忽略
+#[cfg(unix)]
+fn sync_dir(dir: &Path) {
+    let _ = File::open(dir).and_then(|dir| dir.sync_all());
 }
 
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) {}
+
 #[derive(Debug, PartialEq)]
 pub enum FileType {
     Dir,
@@ -80,11 +133,14 @@ pub struct ArchiveFile {
 
     /// 是否将文件拷贝到临时目录用于打包,目前当不复制文件时，不会在压缩包中创建文件结构
     should_copy: bool,
+
+    /// 拷贝或直接压缩时跳过的文件、压缩策略与进度
+    options: ArchiveOptions,
 }
 
 impl ArchiveFile {
     pub fn new(position: Option<String>, path: &Path) -> Result<ArchiveFile, ArchiveError> {
-        let metadata = fs::metadata(path).map_err(ArchiveError::Io)?;
+        let metadata = fs::metadata(path).map_err(|e| ArchiveError::io_at(path, e))?;
         let file_type = FileType::from(&metadata);
 
         Ok(ArchiveFile {
@@ -92,6 +148,7 @@ impl ArchiveFile {
             path: path.to_path_buf(),
             file_type,
             should_copy: true,
+            options: ArchiveOptions::default(),
         })
     }
 
@@ -100,11 +157,29 @@ impl ArchiveFile {
         self
     }
 
+    pub fn exclude(mut self, excludes: ExcludeRules) -> Self {
+        self.options.excludes = excludes;
+        self
+    }
+
+    pub fn compression(mut self, compression: CompressionPolicy) -> Self {
+        self.options.compression = compression;
+        self
+    }
+
+    pub fn progress(mut self, progress: StepProgress) -> Self {
+        self.options.progress = Some(progress);
+        self
+    }
+
     fn copy_file_to(&self, target_path: &Path) -> Result<(), ArchiveError> {
-        if let Some(parent) = target_path.parent() {
-            if !parent.exists() {
-                std::fs::create_dir_all(parent)?;
-            }
+        if self.options.excludes.is_excluded(&self.path, false) {
+            return Ok(());
+        }
+        if let Some(parent) = target_path.parent()
+            && !parent.exists()
+        {
+            std::fs::create_dir_all(parent)?;
         }
         copy_file(&self.path, target_path, &FileCopyOptions::new())?;
         Ok(())
@@ -114,11 +189,29 @@ impl ArchiveFile {
         if !target_path.exists() {
             std::fs::create_dir_all(target_path)?;
         }
-        copy_dir(
-            &self.path,
-            target_path,
-            &DirCopyOptions::new().content_only(true),
-        )?;
+
+        let walker = WalkDir::new(&self.path)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| {
+                !self
+                    .options
+                    .excludes
+                    .is_excluded(entry.path(), entry.file_type().is_dir())
+            });
+        for entry in walker {
+            let entry = entry?;
+            let relative = entry.path().strip_prefix(&self.path).map_err(|e| {
+                ArchiveError::InvalidPath(io::Error::new(io::ErrorKind::InvalidInput, e))
+            })?;
+            let target = target_path.join(relative);
+
+            if entry.file_type().is_dir() {
+                std::fs::create_dir_all(&target)?;
+            } else {
+                copy_file(entry.path(), &target, &FileCopyOptions::new())?;
+            }
+        }
         Ok(())
     }
 
@@ -137,16 +230,16 @@ impl ArchiveFile {
         match self.file_type {
             FileType::File => self.copy_file_to(&target_path),
             FileType::Dir => self.copy_dir_to(&target_path),
-            FileType::Other => Err(ArchiveError::InvalidPath(io::Error::new(
-                io::ErrorKind::InvalidInput,
-                format!("不支持的文件类型:: {:?}", self.path),
-            ))),
+            FileType::Other => Err(ArchiveError::UnsupportedFileType {
+                stage: None,
+                path: self.path.clone(),
+            }),
         }
     }
 
     pub fn zip_with<P: AsRef<Path>>(
         &self,
-        zip_command: &ZipCommand,
+        archiver: &dyn Archiver,
         to: P,
     ) -> Result<(), ArchiveError> {
         if self.should_copy {
@@ -155,81 +248,38 @@ impl ArchiveFile {
             let staging_root = tmp_dir.path();
 
             self.copy_to(staging_root)?;
-            zip_command.zip_dir(to.as_ref(), staging_root)?;
+            // 排除规则已在拷贝时应用
+            let options = ArchiveOptions {
+                excludes: ExcludeRules::none(),
+                ..self.options.clone()
+            };
+            archiver.zip_dir(to.as_ref(), staging_root, &options)?;
         } else {
             // 直接压缩原始路径
             match self.file_type {
                 FileType::File => {
-                    zip_command.zip_file(to.as_ref(), &self.path)?;
+                    archiver.zip_file(to.as_ref(), &self.path, &self.options)?;
                 }
                 FileType::Dir => {
-                    zip_command.zip_dir(to.as_ref(), &self.path)?;
+                    archiver.zip_dir(to.as_ref(), &self.path, &self.options)?;
                 }
                 FileType::Other => {
-                    return Err(ArchiveError::InvalidPath(io::Error::new(
-                        io::ErrorKind::InvalidInput,
-                        format!("不支持的文件类型: {:?}", self.path),
-                    )));
+                    return Err(ArchiveError::UnsupportedFileType {
+                        stage: None,
+                        path: self.path.clone(),
+                    });
                 }
             }
         }
 
         Ok(())
     }
+    /// 压缩到 `to`, 写入过程见 [`zip_atomically`]
     pub fn zip_and_rename<P: AsRef<Path>>(
         &self,
-        zip_command: &ZipCommand,
+        archiver: &dyn Archiver,
         to: P,
     ) -> Result<(), ArchiveError> {
-        let zip_to = to.as_ref().with_extension("zip");
-        self.zip_with(zip_command, &zip_to)?;
-
-        std::fs::rename(&zip_to, &to).inspect_err(|_| {
-            let _ = std::fs::remove_file(&zip_to);
-        })?;
-
-        Ok(())
-    }
-}
-
-#[derive(Debug)]
-pub enum ArchiveError {
-    InvalidPath(std::io::Error),
-    Io(std::io::Error),
-}
-
-impl std::fmt::Display for ArchiveError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ArchiveError::InvalidPath(path) => write!(f, "{path}"),
-            ArchiveError::Io(e) => write!(f, "{e}"),
-        }
-    }
-}
-
-impl From<std::io::Error> for ArchiveError {
-    fn from(err: std::io::Error) -> Self {
-        ArchiveError::Io(err)
-    }
-}
-
-impl From<fs_extra::error::Error> for ArchiveError {
-    fn from(err: fs_extra::error::Error) -> Self {
-        match err.kind {
-            fs_extra::error::ErrorKind::Io(io_err) => ArchiveError::Io(io_err),
-            fs_extra::error::ErrorKind::StripPrefix(e) => {
-                ArchiveError::InvalidPath(std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
-            }
-            fs_extra::error::ErrorKind::OsString(s) => {
-                ArchiveError::InvalidPath(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    format!("Invalid OsString: {s:?}"),
-                ))
-            }
-            _ => ArchiveError::InvalidPath(std::io::Error::other(format!(
-                "fs_extra error: {:?}",
-                err.kind
-            ))),
-        }
+        zip_atomically(to.as_ref(), |zip_to| self.zip_with(archiver, zip_to))
     }
 }
@@ -0,0 +1,291 @@
+use std::fs::{File, Metadata};
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+
+use chrono::{DateTime as ChronoDateTime, Local};
+use serde::Deserialize;
+use walkdir::WalkDir;
+use zip::write::SimpleFileOptions;
+use zip::{DateTime, ZipWriter};
+
+use crate::compression::{CompressionPolicy, SMALL_ENTRY_SIZE};
+use crate::error::ArchiveError;
+use crate::exclude::ExcludeRules;
+use crate::report::StepProgress;
+use crate::zip_command::ZipCommand;
+
+/// 压缩后端, `ArchiveFile`/`ArchiveFiles` 通过它生成 zip 文件
+pub trait Archiver: Send + Sync {
+    /// 将 `from` 目录下的全部内容（不含目录本身）压缩到 `to`, 跳过 `options.excludes` 排除的文件
+    fn zip_dir(&self, to: &Path, from: &Path, options: &ArchiveOptions)
+    -> Result<(), ArchiveError>;
+
+    /// 将单个文件 `from` 以其文件名压缩到 `to` 的根目录
+    fn zip_file(
+        &self,
+        to: &Path,
+        from: &Path,
+        options: &ArchiveOptions,
+    ) -> Result<(), ArchiveError>;
+}
+
+/// 压缩选项
+#[derive(Clone, Default)]
+pub struct ArchiveOptions {
+    /// 跳过的文件
+    pub excludes: ExcludeRules,
+    /// 各条目的压缩策略
+    pub compression: CompressionPolicy,
+    /// 压缩进度, 以已读取的源文件字节数推进
+    pub progress: Option<StepProgress>,
+}
+
+impl ArchiveOptions {
+    fn advance(&self, bytes: u64) {
+        if let Some(progress) = &self.progress {
+            progress.advance(bytes);
+        }
+    }
+}
+
+/// 压缩后端类型
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiverKind {
+    /// 内置压缩
+    #[default]
+    Native,
+    /// 外部 zip 程序
+    Zip,
+}
+
+/// 选择压缩后端
+///
+/// 指定了 `zip_command_path` 或选择 [`ArchiverKind::Zip`] 时使用外部 zip 程序, 否则使用内置的 [`NativeZip`]。
+/// `reproducible` 为可复现构建使用的固定时间戳, 外部 zip 程序无法保证输出一致, 因此不支持该模式。
+pub fn resolve_archiver(
+    kind: ArchiverKind,
+    zip_command_path: Option<&Path>,
+    reproducible: Option<i64>,
+) -> io::Result<Box<dyn Archiver>> {
+    let kind = match zip_command_path {
+        Some(_) => ArchiverKind::Zip,
+        None => kind,
+    };
+
+    match (kind, reproducible) {
+        (ArchiverKind::Zip, Some(_)) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "可复现构建仅支持内置压缩, 请勿同时使用外部 zip 程序",
+        )),
+        (ArchiverKind::Zip, None) => {
+            if let Some(path) = zip_command_path
+                && !path.exists()
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("指定的 zip 程序不存在: {}", path.display()),
+                ));
+            }
+            Ok(Box::new(ZipCommand::resolve(zip_command_path)?))
+        }
+        (ArchiverKind::Native, Some(timestamp)) => {
+            Ok(Box::new(NativeZip::reproducible(timestamp)?))
+        }
+        (ArchiverKind::Native, None) => Ok(Box::new(NativeZip::new())),
+    }
+}
+
+/// 内置的 zip 写入器, 不依赖任何外部程序
+pub struct NativeZip {
+    /// 可复现模式下所有条目使用的固定修改时间, 为 None 时记录文件的实际修改时间
+    fixed_mtime: Option<DateTime>,
+}
+
+impl NativeZip {
+    pub fn new() -> Self {
+        NativeZip { fixed_mtime: None }
+    }
+
+    /// 可复现模式: 固定条目时间、按名称排序条目并统一文件权限
+    pub fn reproducible(timestamp: i64) -> io::Result<Self> {
+        // zip 格式无法表示 1980 年之前的时间
+        let timestamp = timestamp.max(DOS_EPOCH);
+        let datetime = ChronoDateTime::from_timestamp(timestamp, 0).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("无效的时间戳: {timestamp}"),
+            )
+        })?;
+        let fixed_mtime = DateTime::try_from(datetime.naive_utc()).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("时间戳超出 zip 支持范围: {timestamp}, {e}"),
+            )
+        })?;
+
+        Ok(NativeZip {
+            fixed_mtime: Some(fixed_mtime),
+        })
+    }
+
+    fn options(&self, metadata: &Metadata) -> SimpleFileOptions {
+        let options = SimpleFileOptions::default();
+
+        match self.fixed_mtime {
+            Some(mtime) => options
+                .last_modified_time(mtime)
+                .unix_permissions(if metadata.is_dir() { 0o755 } else { 0o644 }),
+            None => match modified_time(metadata) {
+                Some(mtime) => options.last_modified_time(mtime),
+                None => options,
+            },
+        }
+    }
+}
+
+impl Default for NativeZip {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Archiver for NativeZip {
+    fn zip_dir(
+        &self,
+        to: &Path,
+        from: &Path,
+        options: &ArchiveOptions,
+    ) -> Result<(), ArchiveError> {
+        let ArchiveOptions { excludes, .. } = options;
+        let mut writer = ZipWriter::new(File::create(to).map_err(|e| ArchiveError::io_at(to, e))?);
+
+        // 与 zip 命令一致, 符号链接按其指向的文件或目录打包; 链接失效或成环时报错
+        let walker = WalkDir::new(from)
+            .min_depth(1)
+            .follow_links(true)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| !excludes.is_excluded(entry.path(), entry.file_type().is_dir()));
+        for entry in walker {
+            let entry = entry?;
+            let relative = entry
+                .path()
+                .strip_prefix(from)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let name = entry_name(relative);
+            let entry_options = self.options(&entry.metadata()?);
+
+            if entry.file_type().is_dir() {
+                writer.add_directory(name, entry_options)?;
+            } else if entry.file_type().is_file() {
+                let source =
+                    File::open(entry.path()).map_err(|e| ArchiveError::io_at(entry.path(), e))?;
+                write_entry(&mut writer, name, relative, source, entry_options, options)?;
+            } else {
+                return Err(ArchiveError::InvalidPath(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "不支持的文件类型（设备文件、管道等）: {}",
+                        entry.path().display()
+                    ),
+                )));
+            }
+        }
+
+        writer.finish()?;
+        Ok(())
+    }
+
+    fn zip_file(
+        &self,
+        to: &Path,
+        from: &Path,
+        options: &ArchiveOptions,
+    ) -> Result<(), ArchiveError> {
+        let file_name = from.file_name().ok_or_else(|| {
+            ArchiveError::InvalidPath(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("无效的文件路径, 无法获取文件名: [{}]", from.display()),
+            ))
+        })?;
+
+        let source = File::open(from).map_err(|e| ArchiveError::io_at(from, e))?;
+        let entry_options = self.options(&source.metadata()?);
+
+        let mut writer = ZipWriter::new(File::create(to).map_err(|e| ArchiveError::io_at(to, e))?);
+        write_entry(
+            &mut writer,
+            file_name.to_string_lossy().into_owned(),
+            from,
+            source,
+            entry_options,
+            options,
+        )?;
+        writer.finish()?;
+        Ok(())
+    }
+}
+
+/// 按压缩策略写入一个文件条目, 小文件读入内存后再决定是否 deflate
+fn write_entry<W: Write + Seek>(
+    writer: &mut ZipWriter<W>,
+    name: String,
+    path: &Path,
+    source: File,
+    entry_options: SimpleFileOptions,
+    options: &ArchiveOptions,
+) -> Result<(), ArchiveError> {
+    let mut source = ProgressReader::new(source, options);
+    if source.inner.metadata()?.len() < SMALL_ENTRY_SIZE {
+        let mut content = Vec::new();
+        source.read_to_end(&mut content)?;
+        let entry_options = options
+            .compression
+            .apply_to_content(path, &content, entry_options);
+        writer.start_file(name, entry_options)?;
+        writer.write_all(&content)?;
+    } else {
+        writer.start_file(name, options.compression.apply(path, entry_options))?;
+        io::copy(&mut source, writer)?;
+    }
+    Ok(())
+}
+
+/// 读取时推进压缩进度
+struct ProgressReader<'a, R> {
+    inner: R,
+    options: &'a ArchiveOptions,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    fn new(inner: R, options: &'a ArchiveOptions) -> Self {
+        ProgressReader { inner, options }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.options.advance(read as u64);
+        Ok(read)
+    }
+}
+
+/// 1980-01-01T00:00:00Z, zip 格式可表示的最早时间
+const DOS_EPOCH: i64 = 315_532_800;
+
+/// 将文件修改时间转换为 zip 使用的本地时间（与 Info-ZIP 行为一致）
+fn modified_time(metadata: &Metadata) -> Option<DateTime> {
+    let modified: ChronoDateTime<Local> = metadata.modified().ok()?.into();
+    DateTime::try_from(modified.naive_local()).ok()
+}
+
+/// 将相对路径转换为 zip 条目名（统一使用 `/` 作为分隔符）
+pub fn entry_name(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,240 @@
+use console::{Emoji, measure_text_width};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use vivo_lockscreen_packing::{
+    archiver::Archiver,
+    check::check_lockscreen_package,
+    error::{ArchiveError, Stage},
+    output::{OnConflict, output_date, package_dirname, static_output_name},
+    pipeline::{PackOptions, PackOutput, process_lockscreen_package, validate_package},
+    plan::{PackPlan, plan_lockscreen_package},
+};
+use walkdir::WalkDir;
+
+static SUCCESS: Emoji<'_, '_> = Emoji("✅ ", "OK ");
+static FAILURE: Emoji<'_, '_> = Emoji("❌ ", "ERR");
+
+/// 单个锁屏包的打包任务, 已合并命令行参数与锁屏包的 vlp.toml
+pub struct PackJob {
+    pub input: PathBuf,
+    pub output_dir: PathBuf,
+    pub archiver: Box<dyn Archiver>,
+    pub options: PackOptions,
+    /// 打包前是否执行 [`check_lockscreen_package`] 深度检查
+    pub check: bool,
+}
+
+impl PackJob {
+    /// 校验锁屏包后执行打包
+    pub fn run(&self) -> Result<PackOutput, ArchiveError> {
+        self.validate()?;
+        process_lockscreen_package(
+            &self.input,
+            &self.output_dir,
+            self.archiver.as_ref(),
+            &self.options,
+        )
+    }
+
+    /// 只计算打包计划, 不写入任何文件
+    pub fn plan(&self) -> Result<PackPlan, ArchiveError> {
+        self.validate()?;
+        plan_lockscreen_package(&self.input, &self.output_dir, &self.options)
+    }
+
+    fn validate(&self) -> Result<(), ArchiveError> {
+        validate_package(&self.input)?;
+        if self.check {
+            let report = check_lockscreen_package(&self.input, &self.options.exclude)?;
+            for warning in &report.warnings {
+                self.options
+                    .reporter
+                    .warning(&self.input, &warning.to_string());
+            }
+            report.into_result()?;
+        }
+        Ok(())
+    }
+}
+
+/// 单个锁屏包的打包结果
+pub struct PackResult {
+    pub input: PathBuf,
+    pub result: Result<PackOutput, ArchiveError>,
+}
+
+/// 查找 `root` 下所有满足 [`validate_package`] 的目录（找到后不再深入其子目录）
+pub fn discover_packages(root: &Path) -> Result<Vec<PathBuf>, ArchiveError> {
+    let mut packages = Vec::new();
+    let mut walker = WalkDir::new(root).sort_by_file_name().into_iter();
+
+    while let Some(entry) = walker.next() {
+        let entry = entry.map_err(io::Error::from)?;
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        if validate_package(entry.path()).is_ok() {
+            packages.push(entry.into_path());
+            walker.skip_current_dir();
+        }
+    }
+
+    Ok(packages)
+}
+
+/// 批量打包时未指定输出文件名模板时使用的默认值, 同一目录下的多个锁屏包不会写入同一个文件
+pub const BATCH_OUTPUT_NAME: &str = "{dirname}-lockscreen";
+
+/// 使用最多 `workers` 个线程并行打包, 单个锁屏包失败（包括生成任务失败）不影响其余任务
+///
+/// 打包前检查文件名可预先确定的任务: 除 [`OnConflict::Suffix`] 外, 与之前的任务输出路径相同的任务直接失败,
+/// 不会相互覆盖。返回结果的顺序与 `inputs` 一致。
+pub fn pack_all<F>(inputs: &[PathBuf], workers: usize, make_job: F) -> Vec<PackResult>
+where
+    F: Fn(&Path) -> Result<PackJob, ArchiveError>,
+{
+    let mut results = Vec::with_capacity(inputs.len());
+    let mut queue = Vec::with_capacity(inputs.len());
+    let mut claimed: HashMap<PathBuf, PathBuf> = HashMap::new();
+    for (index, input) in inputs.iter().enumerate() {
+        let job = make_job(input).and_then(|job| {
+            if let Some(output) = static_output_path(&job)
+                && job.options.on_conflict != OnConflict::Suffix
+            {
+                if let Some(first) = claimed.get(&output) {
+                    return Err(duplicate_output(output, first));
+                }
+                claimed.insert(output, input.clone());
+            }
+            Ok(job)
+        });
+        match job {
+            Ok(job) => queue.push((index, job)),
+            Err(e) => results.push((
+                index,
+                PackResult {
+                    input: input.clone(),
+                    result: Err(e),
+                },
+            )),
+        }
+    }
+
+    // 按输入顺序取出任务
+    queue.reverse();
+    let queue = Mutex::new(queue);
+    let results = Mutex::new(results);
+
+    thread::scope(|scope| {
+        for _ in 0..workers.max(1) {
+            scope.spawn(|| {
+                loop {
+                    let Some((index, job)) = queue.lock().unwrap().pop() else {
+                        break;
+                    };
+                    let result = job.run();
+                    results.lock().unwrap().push((
+                        index,
+                        PackResult {
+                            input: job.input,
+                            result,
+                        },
+                    ));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(index, _)| *index);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// 文件名不依赖编号与标题时, 打包前即可确定的输出路径
+fn static_output_path(job: &PackJob) -> Option<PathBuf> {
+    let name = static_output_name(
+        job.options.output_name(),
+        &output_date(job.options.source_date_epoch),
+        &package_dirname(&job.input),
+    )?;
+    let output_dir = job
+        .output_dir
+        .canonicalize()
+        .unwrap_or_else(|_| job.output_dir.clone());
+    Some(output_dir.join(name))
+}
+
+fn duplicate_output(output: PathBuf, first: &Path) -> ArchiveError {
+    ArchiveError::OutputWrite {
+        stage: Some(Stage::Output),
+        source: io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "与 {} 的输出路径相同（使用 --output-name 区分文件名或 --auto-suffix 自动改名）",
+                first.display()
+            ),
+        ),
+        path: output,
+    }
+}
+
+/// 打印批量打包的汇总表
+pub fn print_summary(results: &[PackResult]) {
+    let rows: Vec<[String; 4]> = results
+        .iter()
+        .map(|result| match &result.result {
+            Ok(output) => [
+                format!("{SUCCESS}成功"),
+                output.id.clone(),
+                result.input.display().to_string(),
+                output.output.display().to_string(),
+            ],
+            Err(e) => [
+                format!("{FAILURE}失败"),
+                "-".to_string(),
+                result.input.display().to_string(),
+                // 多行的错误（如 zip 程序的输出）在表格后完整列出
+                e.to_string().lines().next().unwrap_or_default().to_string(),
+            ],
+        })
+        .collect();
+
+    let header = ["状态", "编号", "锁屏包", "输出路径/错误"].map(str::to_string);
+    let mut widths = [0usize; 3];
+    for row in std::iter::once(&header).chain(&rows) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(measure_text_width(cell));
+        }
+    }
+
+    println!();
+    for row in std::iter::once(&header).chain(&rows) {
+        let mut line = String::new();
+        for (width, cell) in widths.iter().zip(row) {
+            line.push_str(cell);
+            line.push_str(&" ".repeat(width - measure_text_width(cell) + 2));
+        }
+        line.push_str(&row[3]);
+        println!("{line}");
+    }
+
+    for result in results {
+        if let Err(e) = &result.result {
+            let message = e.to_string();
+            if message.contains('\n') {
+                println!("\n{}:\n{message}", result.input.display());
+            }
+        }
+    }
+
+    let failed = results.iter().filter(|r| r.result.is_err()).count();
+    println!(
+        "\n共 {} 个锁屏包, 成功 {}, 失败 {}",
+        results.len(),
+        results.len() - failed,
+        failed
+    );
+}
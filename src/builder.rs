@@ -0,0 +1,167 @@
+use crate::{
+    archiver::{Archiver, ArchiverKind, resolve_archiver},
+    compression::CompressionPolicy,
+    description::{DescriptionEdits, LocalizedText, TitleStrategy},
+    error::ArchiveError,
+    id::{IdMode, IdPolicy},
+    output::{OnConflict, parent_output_dir},
+    pipeline::{PackOptions, PackOutput, process_lockscreen_package, validate_package},
+    report::Reporter,
+};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// 锁屏包打包器, 供其他程序直接调用而无需启动 `vlp`
+///
+/// 未指定的选项与命令行的默认值一致: 输出到锁屏包的上一级目录, 使用内置压缩,
+/// 不输出任何内容。锁屏包中的 `vlp.toml` 不会被读取。
+///
+/// ```no_run
+/// use vivo_lockscreen_packing::PackageBuilder;
+///
+/// let packed = PackageBuilder::new("themes/spring")
+///     .output_dir("dist")
+///     .title("zh_CN", "春日")
+///     .build()?;
+/// println!("{} -> {} ({} 字节)", packed.id, packed.output.display(), packed.sizes.output);
+/// # Ok::<(), vivo_lockscreen_packing::ArchiveError>(())
+/// ```
+pub struct PackageBuilder {
+    input: PathBuf,
+    output_dir: Option<PathBuf>,
+    archiver: Option<Box<dyn Archiver>>,
+    options: PackOptions,
+}
+
+impl PackageBuilder {
+    /// `input` 为锁屏包目录（包含 preview、description.xml、lockscreen/manifest.xml）
+    pub fn new<P: AsRef<Path>>(input: P) -> Self {
+        PackageBuilder {
+            input: input.as_ref().to_path_buf(),
+            output_dir: None,
+            archiver: None,
+            options: PackOptions::default(),
+        }
+    }
+
+    /// 输出目录, 默认为锁屏包的上一级目录, 不存在时自动创建
+    pub fn output_dir<P: AsRef<Path>>(mut self, dir: P) -> Self {
+        self.output_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// 输出文件名模板, 默认为 "lockscreen", 支持 `{title}`、`{id}`、`{date}`、`{dirname}`
+    pub fn output_name(mut self, name: impl Into<String>) -> Self {
+        self.options.output_name = Some(name.into());
+        self
+    }
+
+    /// 输出文件已存在时的处理方式, 默认覆盖
+    pub fn on_conflict(mut self, on_conflict: OnConflict) -> Self {
+        self.options.on_conflict = on_conflict;
+        self
+    }
+
+    /// 覆盖输出文件前将其保留为 `<输出文件>.bak`
+    pub fn backup(mut self, backup: bool) -> Self {
+        self.options.backup = backup;
+        self
+    }
+
+    /// 锁屏编号, 默认沿用 vlp.lock 中的编号或自动生成
+    pub fn id(mut self, id: impl Into<String>) -> Self {
+        self.options.id = Some(id.into());
+        self
+    }
+
+    /// 未指定编号时编号的来源, 默认沿用 vlp.lock 中的编号
+    pub fn id_mode(mut self, mode: IdMode) -> Self {
+        self.options.id_mode = mode;
+        self
+    }
+
+    /// 需要新编号时的分配规则, 默认随机分配且只避开输出目录中已有锁屏文件的编号
+    pub fn id_policy(mut self, ids: IdPolicy) -> Self {
+        self.options.ids = ids;
+        self
+    }
+
+    /// 已有标题的处理方式, 默认只将 zh_CN 标题设为锁屏编号
+    pub fn title_strategy(mut self, strategy: TitleStrategy) -> Self {
+        self.options.description.title_strategy = strategy;
+        self
+    }
+
+    /// 设置某个语言的标题, 如 `title("en_US", "Spring")`
+    pub fn title(mut self, locale: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.description.titles.push(LocalizedText {
+            locale: locale.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// 设置某个语言的简介
+    pub fn description(mut self, locale: impl Into<String>, value: impl Into<String>) -> Self {
+        self.options.description.descriptions.push(LocalizedText {
+            locale: locale.into(),
+            value: value.into(),
+        });
+        self
+    }
+
+    /// 对 description.xml 的全部修改, 覆盖之前的 [`title`](Self::title)、[`description`](Self::description)
+    /// 与 [`title_strategy`](Self::title_strategy)
+    pub fn description_edits(mut self, edits: DescriptionEdits) -> Self {
+        self.options.description = edits;
+        self
+    }
+
+    /// 压缩后端, 默认为内置压缩
+    pub fn archiver(mut self, archiver: Box<dyn Archiver>) -> Self {
+        self.archiver = Some(archiver);
+        self
+    }
+
+    /// 打包进度的输出方式, 默认不输出
+    pub fn reporter(mut self, reporter: Arc<dyn Reporter>) -> Self {
+        self.options.reporter = reporter;
+        self
+    }
+
+    /// 各条目的压缩策略
+    pub fn compression(mut self, compression: CompressionPolicy) -> Self {
+        self.options.compression = compression;
+        self
+    }
+
+    /// 追加一条排除规则（gitignore 语法）
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.options.exclude.push(pattern.into());
+        self
+    }
+
+    /// 可复现构建, 使用 `timestamp` 作为所有条目的修改时间并据此生成编号
+    ///
+    /// 同时指定了 [`archiver`](Self::archiver) 时由该后端决定条目时间。
+    pub fn reproducible(mut self, timestamp: i64) -> Self {
+        self.options.source_date_epoch = Some(timestamp);
+        self
+    }
+
+    /// 校验锁屏包并打包
+    pub fn build(self) -> Result<PackOutput, ArchiveError> {
+        validate_package(&self.input)?;
+
+        let output_dir = self
+            .output_dir
+            .unwrap_or_else(|| parent_output_dir(&self.input));
+        let archiver = match self.archiver {
+            Some(archiver) => archiver,
+            None => resolve_archiver(ArchiverKind::Native, None, self.options.source_date_epoch)
+                .map_err(ArchiveError::InvalidPath)?,
+        };
+
+        process_lockscreen_package(&self.input, &output_dir, archiver.as_ref(), &self.options)
+    }
+}
@@ -0,0 +1,574 @@
+use crate::{
+    archiver::entry_name,
+    description::read_field_positions,
+    error::{ArchiveError, Stage},
+    exclude::{ExcludeRules, IGNORE_FILE_NAME},
+};
+use quick_xml::Reader;
+use quick_xml::events::{BytesStart, Event};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+const BOM: &str = "\u{feff}";
+
+/// 需要检查格式的 XML 文件
+const XML_FILES: [&str; 2] = ["description.xml", "lockscreen/manifest.xml"];
+
+/// 支持的预览图格式
+const PREVIEW_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp"];
+
+/// lockscreen 目录中常见的文件类型: 布局与脚本、图片、音视频与字体, 其余类型只给出警告
+const LOCKSCREEN_EXTENSIONS: &[&str] = &[
+    "xml", "json", "txt", "lua", "js", // 布局与脚本
+    "png", "jpg", "jpeg", "webp", "gif", "bmp", "svg", // 图片
+    "mp3", "ogg", "wav", "m4a", "aac", "amr", "mp4", "webm", "3gp", // 音视频
+    "ttf", "otf", // 字体
+];
+
+/// 检查发现的一个问题
+#[derive(Debug, Clone, Serialize)]
+pub struct Problem {
+    /// 相对于锁屏包根目录的路径
+    pub path: String,
+    /// 从 1 开始的行号, 与具体行无关的问题为 None
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Problem {
+    fn new(path: &str, line: Option<usize>, message: impl Into<String>) -> Self {
+        Problem {
+            path: path.to_string(),
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+/// [`check_lockscreen_package`] 的结果
+#[derive(Debug, Serialize)]
+pub struct CheckReport {
+    pub input: PathBuf,
+    pub problems: Vec<Problem>,
+    /// 不影响检查结果的问题, 如 lockscreen 目录中不常见的文件类型
+    pub warnings: Vec<Problem>,
+}
+
+impl CheckReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+
+    /// 有问题时转为 [`ArchiveError::CheckFailed`]
+    pub fn into_result(self) -> Result<(), ArchiveError> {
+        if self.is_ok() {
+            return Ok(());
+        }
+        Err(ArchiveError::CheckFailed {
+            package: self.input,
+            problems: self.problems,
+        })
+    }
+}
+
+/// 深度检查锁屏包源目录, 一次性列出所有问题
+///
+/// 检查 description.xml 与 lockscreen/manifest.xml 是否为格式正确的 XML、description.xml 是否包含必需字段、
+/// preview 中是否至少有一张支持的预览图, 以及 lockscreen 与 preview 中是否有空目录、空文件或不支持的文件类型。
+/// lockscreen 中不常见的文件类型记为警告, 不影响检查结果。
+/// 被排除的文件（见 [`ExcludeRules`]）不会被打包, 也不参与检查。
+pub fn check_lockscreen_package(
+    input: &Path,
+    exclude: &[String],
+) -> Result<CheckReport, ArchiveError> {
+    let excludes = ExcludeRules::load(input, exclude).map_err(|e| {
+        ArchiveError::io_at(input.join(IGNORE_FILE_NAME), e).in_stage(Stage::Validate)
+    })?;
+    let mut problems = Vec::new();
+    let mut warnings = Vec::new();
+
+    for name in XML_FILES {
+        let path = input.join(name);
+        if !path.is_file() {
+            problems.push(Problem::new(name, None, "缺少必要文件"));
+            continue;
+        }
+        let bytes = fs::read(&path).map_err(|e| ArchiveError::io_at(&path, e))?;
+        if let Some(content) = check_xml(name, &bytes, &mut problems)
+            && name == "description.xml"
+        {
+            check_description(name, content, &mut problems);
+        }
+    }
+
+    for (name, _) in check_files(input, "lockscreen", &excludes, &mut problems)? {
+        match extension(&name) {
+            Some(ext) if LOCKSCREEN_EXTENSIONS.contains(&ext.as_str()) => {}
+            Some(ext) => warnings.push(Problem::new(
+                &name,
+                None,
+                format!("不常见的文件类型 .{ext}, 请确认锁屏能够使用"),
+            )),
+            None => warnings.push(Problem::new(&name, None, "文件没有扩展名")),
+        }
+    }
+
+    let mut images = 0;
+    let preview = check_files(input, "preview", &excludes, &mut problems)?;
+    let has_preview_dir = input.join("preview").is_dir();
+    for (name, path) in preview {
+        match extension(&name) {
+            Some(ext) if PREVIEW_EXTENSIONS.contains(&ext.as_str()) => {
+                match imagesize::size(&path) {
+                    Ok(_) => images += 1,
+                    Err(e) => {
+                        problems.push(Problem::new(&name, None, format!("无法识别的图片: {e}")))
+                    }
+                }
+            }
+            _ => problems.push(Problem::new(
+                &name,
+                None,
+                format!("不支持的预览图格式, 应为 {}", PREVIEW_EXTENSIONS.join("/")),
+            )),
+        }
+    }
+    if has_preview_dir && images == 0 {
+        problems.push(Problem::new("preview", None, "没有可用的预览图"));
+    }
+
+    Ok(CheckReport {
+        input: input.to_path_buf(),
+        problems,
+        warnings,
+    })
+}
+
+/// 检查 `dir` 下未被排除的条目, 记录空目录、空文件与非普通文件, 返回其余文件的 (条目名, 路径)
+fn check_files(
+    input: &Path,
+    dir: &str,
+    excludes: &ExcludeRules,
+    problems: &mut Vec<Problem>,
+) -> Result<Vec<(String, PathBuf)>, ArchiveError> {
+    let root = input.join(dir);
+    if !root.is_dir() {
+        problems.push(Problem::new(dir, None, "缺少必要目录"));
+        return Ok(Vec::new());
+    }
+
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    let mut non_empty = HashSet::new();
+    let walker = WalkDir::new(&root)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| {
+            entry.depth() == 0 || !excludes.is_excluded(entry.path(), entry.file_type().is_dir())
+        });
+    for entry in walker {
+        let entry = entry?;
+        let relative = entry.path().strip_prefix(input).unwrap_or(entry.path());
+        let name = entry_name(relative);
+        if let Some(parent) = entry.path().parent() {
+            non_empty.insert(parent.to_path_buf());
+        }
+
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            dirs.push((name, entry.into_path()));
+        } else if !metadata.is_file() {
+            problems.push(Problem::new(
+                &name,
+                None,
+                "不支持的文件类型（设备文件、管道等）",
+            ));
+        } else if metadata.len() == 0 {
+            problems.push(Problem::new(&name, None, "文件为空"));
+        } else {
+            files.push((name, entry.into_path()));
+        }
+    }
+
+    for (name, path) in dirs {
+        if !non_empty.contains(&path) {
+            problems.push(Problem::new(&name, None, "目录为空"));
+        }
+    }
+    Ok(files)
+}
+
+/// 检查 XML 是否格式正确, 正确时返回文件内容（保留 BOM, 与行号计算一致）
+fn check_xml<'a>(name: &str, bytes: &'a [u8], problems: &mut Vec<Problem>) -> Option<&'a str> {
+    let content = match std::str::from_utf8(bytes) {
+        Ok(content) => content,
+        Err(e) => {
+            let line = line_at(bytes, e.valid_up_to());
+            problems.push(Problem::new(name, Some(line), "不是有效的 UTF-8 文本"));
+            return None;
+        }
+    };
+    let body = content.strip_prefix(BOM).unwrap_or(content);
+    let offset = content.len() - body.len();
+    let line = |position: usize| Some(line_at(content.as_bytes(), offset + position));
+
+    let mut reader = Reader::from_str(body);
+    let mut open: Vec<(String, usize)> = Vec::new();
+    let mut roots = 0;
+    let mut valid = true;
+    loop {
+        let before = reader.buffer_position() as usize;
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(e) => {
+                let position = reader.error_position() as usize;
+                problems.push(Problem::new(
+                    name,
+                    line(position),
+                    format!("XML 格式错误: {e}"),
+                ));
+                return None;
+            }
+        };
+        let opens = matches!(event, Event::Start(_));
+        match event {
+            Event::Start(start) | Event::Empty(start) => {
+                if open.is_empty() {
+                    roots += 1;
+                    if roots == 2 {
+                        problems.push(Problem::new(name, line(before), "存在多个根元素"));
+                        valid = false;
+                    }
+                }
+                if let Err(message) = check_attributes(&start) {
+                    problems.push(Problem::new(name, line(before), message));
+                    valid = false;
+                }
+                if opens {
+                    let tag = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+                    open.push((tag, before));
+                }
+            }
+            Event::End(_) => {
+                open.pop();
+            }
+            Event::Text(text) => {
+                if open.is_empty() && text.iter().any(|byte| !byte.is_ascii_whitespace()) {
+                    problems.push(Problem::new(name, line(before), "根元素之外存在文本"));
+                    valid = false;
+                } else if let Err(e) = text.unescape() {
+                    problems.push(Problem::new(
+                        name,
+                        line(before),
+                        format!("XML 格式错误: {e}"),
+                    ));
+                    valid = false;
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    if let Some((tag, position)) = open.last() {
+        problems.push(Problem::new(
+            name,
+            line(*position),
+            format!("元素 <{tag}> 未闭合"),
+        ));
+        return None;
+    }
+    if roots == 0 {
+        problems.push(Problem::new(name, None, "缺少根元素"));
+        return None;
+    }
+    valid.then_some(content)
+}
+
+/// 属性格式正确且属性值中的实体引用有效
+fn check_attributes(start: &BytesStart<'_>) -> Result<(), String> {
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|e| format!("属性格式错误: {e}"))?;
+        attribute
+            .unescape_value()
+            .map_err(|e| format!("属性值格式错误: {e}"))?;
+    }
+    Ok(())
+}
+
+/// description.xml 的必需字段, 规则与打包时的 Step 2 一致
+fn check_description(name: &str, content: &str, problems: &mut Vec<Problem>) {
+    let Ok(fields) = read_field_positions(content) else {
+        return;
+    };
+    let line = |position: usize| Some(line_at(content.as_bytes(), position));
+
+    match fields.iter().find(|(field, _)| field.name == "id") {
+        None => problems.push(Problem::new(name, None, "缺少 <id> 元素")),
+        Some((field, position)) if field.value.trim().is_empty() => {
+            problems.push(Problem::new(name, line(*position), "<id> 为空"))
+        }
+        Some(_) => {}
+    }
+
+    let zh_cn_title = fields
+        .iter()
+        .any(|(field, _)| field.name == "title" && field.locale.as_deref() == Some("zh_CN"));
+    if !zh_cn_title {
+        problems.push(Problem::new(
+            name,
+            None,
+            r#"缺少 <title locale="zh_CN"> 元素"#,
+        ));
+    }
+
+    for (field, position) in &fields {
+        let localized = field.name == "title" || field.name == "description";
+        if localized
+            && field
+                .locale
+                .as_deref()
+                .is_some_and(|locale| locale.trim().is_empty())
+        {
+            problems.push(Problem::new(
+                name,
+                line(*position),
+                format!("<{}> 的 locale 为空", field.name),
+            ));
+        }
+    }
+}
+
+/// `position` 所在的行号, 从 1 开始
+fn line_at(bytes: &[u8], position: usize) -> usize {
+    let end = position.min(bytes.len());
+    bytes[..end].iter().filter(|&&byte| byte == b'\n').count() + 1
+}
+
+/// 小写的扩展名
+fn extension(name: &str) -> Option<String> {
+    Path::new(name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase())
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{line}: {}", self.path, self.message),
+            None => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+impl fmt::Display for CheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            writeln!(f, "{}: 检查通过", self.input.display())?;
+        } else {
+            writeln!(
+                f,
+                "{}: 发现 {} 个问题",
+                self.input.display(),
+                self.problems.len()
+            )?;
+            for problem in &self.problems {
+                writeln!(f, "  {problem}")?;
+            }
+        }
+        for warning in &self.warnings {
+            writeln!(f, "  警告: {warning}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const DESCRIPTION: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<theme>\n    <id>1</id>\n    <title locale=\"zh_CN\">测试</title>\n</theme>\n";
+
+    /// 只有文件签名与 IHDR 的 1x1 PNG, 足以读出预览图尺寸
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR\0\0\0\x01\0\0\0\x01";
+
+    fn write_package(root: &Path) {
+        fs::create_dir_all(root.join("preview")).unwrap();
+        fs::create_dir_all(root.join("lockscreen")).unwrap();
+        fs::write(root.join("description.xml"), DESCRIPTION).unwrap();
+        fs::write(root.join("lockscreen/manifest.xml"), "<Lockscreen/>\n").unwrap();
+        fs::write(root.join("preview/preview_0.png"), PNG).unwrap();
+    }
+
+    fn check(root: &Path) -> CheckReport {
+        check_lockscreen_package(root, &[]).unwrap()
+    }
+
+    fn xml_problems(content: &str) -> Vec<(Option<usize>, String)> {
+        let mut problems = Vec::new();
+        if let Some(content) = check_xml("description.xml", content.as_bytes(), &mut problems) {
+            check_description("description.xml", content, &mut problems);
+        }
+        problems
+            .into_iter()
+            .map(|problem| (problem.line, problem.message))
+            .collect()
+    }
+
+    #[test]
+    fn valid_package_passes() {
+        let tmp = TempDir::new().unwrap();
+        write_package(tmp.path());
+        let report = check(tmp.path());
+        assert!(report.is_ok(), "{report}");
+        assert!(report.warnings.is_empty(), "{report}");
+        assert!(report.into_result().is_ok());
+    }
+
+    #[test]
+    fn unclosed_element_reports_its_line() {
+        let problems =
+            xml_problems("<theme>\n  <id>1</id>\n  <title locale=\"zh_CN\">t\n</theme>\n");
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert_eq!(problems[0].0, Some(4));
+        assert!(problems[0].1.starts_with("XML 格式错误"), "{problems:?}");
+
+        let problems = xml_problems("<theme>\n  <id>1</id>\n");
+        assert_eq!(problems, [(Some(1), "元素 <theme> 未闭合".to_string())]);
+    }
+
+    #[test]
+    fn line_numbers_count_from_after_bom() {
+        let problems = xml_problems(
+            "\u{feff}<theme>\n<id>1</id>\n<title locale=\"zh_CN\">t</title>\n</theme>\n<extra/>\n",
+        );
+        assert_eq!(problems, [(Some(5), "存在多个根元素".to_string())]);
+    }
+
+    #[test]
+    fn text_outside_root_and_bad_attributes() {
+        let problems = xml_problems(
+            "<theme>\n<id>1</id>\n<title locale=\"zh_CN\">t</title>\n</theme>\noops\n",
+        );
+        assert_eq!(problems, [(Some(4), "根元素之外存在文本".to_string())]);
+
+        let problems = xml_problems(
+            "<theme>\n<id>1</id>\n<title locale=\"zh_CN\" a=\"&bad;\">t</title>\n</theme>\n",
+        );
+        assert_eq!(problems.len(), 1, "{problems:?}");
+        assert_eq!(problems[0].0, Some(3));
+        assert!(problems[0].1.starts_with("属性值格式错误"), "{problems:?}");
+    }
+
+    #[test]
+    fn invalid_utf8_reports_its_line() {
+        let mut problems = Vec::new();
+        assert!(
+            check_xml(
+                "description.xml",
+                b"<theme>\n<id>\xff</id>\n</theme>",
+                &mut problems
+            )
+            .is_none()
+        );
+        assert_eq!(problems[0].line, Some(2));
+        assert_eq!(problems[0].message, "不是有效的 UTF-8 文本");
+    }
+
+    #[test]
+    fn missing_and_empty_description_fields() {
+        let problems = xml_problems("<theme>\n  <author>me</author>\n</theme>\n");
+        assert_eq!(
+            problems,
+            [
+                (None, "缺少 <id> 元素".to_string()),
+                (None, r#"缺少 <title locale="zh_CN"> 元素"#.to_string()),
+            ]
+        );
+
+        let problems = xml_problems(
+            "<theme>\n  <id> </id>\n  <title locale=\"zh_CN\">t</title>\n  <title locale=\"\">x</title>\n</theme>\n",
+        );
+        assert_eq!(
+            problems,
+            [
+                (Some(2), "<id> 为空".to_string()),
+                (Some(4), "<title> 的 locale 为空".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_files_and_directories() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        write_package(root);
+        fs::remove_file(root.join("lockscreen/manifest.xml")).unwrap();
+        fs::create_dir_all(root.join("lockscreen/empty")).unwrap();
+        fs::write(root.join("lockscreen/blank.png"), "").unwrap();
+        fs::write(root.join("preview/preview_0.png"), "not a png").unwrap();
+        fs::write(root.join("preview/notes.txt"), "x").unwrap();
+
+        let report = check(root);
+        let problems: Vec<String> = report.problems.iter().map(Problem::to_string).collect();
+        let expected = [
+            "lockscreen/manifest.xml: 缺少必要文件",
+            "lockscreen/blank.png: 文件为空",
+            "lockscreen/empty: 目录为空",
+            "preview/notes.txt: 不支持的预览图格式, 应为 png/jpg/jpeg/webp",
+            // 其后为 imagesize 的错误信息
+            "preview/preview_0.png: 无法识别的图片: ",
+            "preview: 没有可用的预览图",
+        ];
+        assert_eq!(problems.len(), expected.len(), "{problems:?}");
+        for (problem, expected) in problems.iter().zip(expected) {
+            assert!(problem.starts_with(expected), "{problem}");
+        }
+        assert!(matches!(
+            report.into_result(),
+            Err(ArchiveError::CheckFailed { problems, .. }) if problems.len() == 6
+        ));
+    }
+
+    #[test]
+    fn unknown_lockscreen_extensions_are_warnings() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        write_package(root);
+        fs::write(root.join("lockscreen/model.glb"), "x").unwrap();
+        fs::write(root.join("lockscreen/LICENSE"), "x").unwrap();
+        fs::write(root.join("lockscreen/bg.PNG"), PNG).unwrap();
+
+        let report = check(root);
+        assert!(report.is_ok(), "{report}");
+        let warnings: Vec<String> = report.warnings.iter().map(Problem::to_string).collect();
+        assert_eq!(
+            warnings,
+            [
+                "lockscreen/LICENSE: 文件没有扩展名",
+                "lockscreen/model.glb: 不常见的文件类型 .glb, 请确认锁屏能够使用",
+            ]
+            .map(str::to_string)
+        );
+    }
+
+    #[test]
+    fn excluded_files_are_not_checked() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        write_package(root);
+        fs::write(root.join("lockscreen/notes.md"), "").unwrap();
+        fs::create_dir_all(root.join("lockscreen/.git")).unwrap();
+
+        let report =
+            check_lockscreen_package(root, &["*.md".to_string(), ".git/".to_string()]).unwrap();
+        assert!(report.is_ok(), "{report}");
+        assert!(report.warnings.is_empty(), "{report}");
+    }
+}
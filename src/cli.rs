@@ -1,81 +1,563 @@
 // src/cli.rs
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::collections::BTreeMap;
 use std::io;
 use std::path::{Path, PathBuf};
 
-use crate::archivefiles::ArchiveError;
+use std::sync::Arc;
+
+use crate::batch::discover_packages;
+use vivo_lockscreen_packing::archiver::{Archiver, ArchiverKind, resolve_archiver};
+use vivo_lockscreen_packing::compression::{CompressionPolicy, DEFAULT_LEVEL};
+use vivo_lockscreen_packing::config::PackageConfig;
+use vivo_lockscreen_packing::description::{
+    DescriptionEdits, LocalizedText, TitleStrategy, parse_localized,
+};
+use vivo_lockscreen_packing::error::ArchiveError;
+use vivo_lockscreen_packing::id::{
+    IdMode, IdPolicy, IdStrategy, IdTemplate, default_registry_path,
+};
+use vivo_lockscreen_packing::output::{OnConflict, parent_output_dir, validate_output_name};
+use vivo_lockscreen_packing::pipeline::PackOptions;
+use vivo_lockscreen_packing::report::Reporter;
+use vivo_lockscreen_packing::utils::{PauseMode, validate_id};
 
 /// VIVO锁屏打包工具
+///
+/// 未指定子命令时按 `pack` 处理, 将锁屏包拖到程序上即可打包。
 #[derive(Parser, Debug)]
-#[command(author = "mdjso", version = "1.1", about = "用于打包锁屏主题的工具")]
-pub struct Args {
-    /// 锁屏包路径（必须包含 preview、description.xml、lockscreen/manifest.xml）
+#[command(
+    author = "mdjso",
+    version = "1.1",
+    about = "用于打包锁屏主题的工具",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// 未指定子命令时的打包参数
+    #[command(flatten)]
+    pub pack: PackArgs,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+}
+
+impl Cli {
+    /// 要执行的子命令, 未指定时为 `pack`
+    pub fn into_command(self) -> (Command, GlobalArgs) {
+        let command = self.command.unwrap_or(Command::Pack(Box::new(self.pack)));
+        (command, self.global)
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// 打包锁屏包（默认, 可省略子命令直接指定锁屏包路径）
+    Pack(Box<PackArgs>),
+    /// 监听锁屏包目录, 文件变化时自动重新打包（参数与 pack 相同, 等同于 `pack -w`）
+    Watch(Box<PackArgs>),
+    /// 将打包好的 lockscreen 文件还原为可编辑的锁屏包
+    Unpack(UnpackArgs),
+    /// 深度检查锁屏包源目录, 一次性列出所有问题（打包前也会执行）
+    Check(CheckArgs),
+    /// 查看 lockscreen 文件的描述信息与条目列表
+    #[command(visible_alias = "ls")]
+    Info(InfoArgs),
+    /// 注册右键菜单项
+    Register(RegisterArgs),
+    /// 取消注册右键菜单项
+    Unregister(RegisterArgs),
+}
+
+impl Command {
+    /// 是否以 JSON 格式输出结果
+    pub fn json(&self) -> bool {
+        match self {
+            Command::Pack(args) => args.json,
+            Command::Check(args) => args.json,
+            Command::Info(args) => args.json,
+            Command::Watch(_)
+            | Command::Unpack(_)
+            | Command::Register(_)
+            | Command::Unregister(_) => false,
+        }
+    }
+}
+
+/// 所有子命令共用的参数
+#[derive(clap::Args, Debug, Clone)]
+pub struct GlobalArgs {
+    /// 输出详细信息
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "输出详细信息, 如打包时被排除的文件"
+    )]
+    pub verbose: bool,
+
+    /// 只输出错误
+    #[arg(
+        short,
+        long,
+        global = true,
+        conflicts_with = "verbose",
+        help = "不输出进度, 只输出错误"
+    )]
+    pub quiet: bool,
+
+    /// 出错时总是等待按回车后再退出（默认仅在拖拽或右键菜单启动时等待）
+    #[arg(long, global = true, help = "出错时等待按回车后再退出")]
+    pub pause: bool,
+
+    /// 出错时直接退出, 不等待按回车
+    #[arg(
+        long,
+        global = true,
+        conflicts_with = "pause",
+        help = "出错时直接退出, 不等待按回车"
+    )]
+    pub no_pause: bool,
+
+    /// 禁用彩色输出, 设置了 NO_COLOR 环境变量时自动启用
+    #[arg(long, global = true, help = "禁用彩色输出")]
+    pub no_color: bool,
+}
+
+impl GlobalArgs {
+    pub fn pause_mode(&self) -> PauseMode {
+        if self.pause {
+            PauseMode::Always
+        } else if self.no_pause {
+            PauseMode::Never
+        } else {
+            PauseMode::Auto
+        }
+    }
+
+    /// 是否使用彩色输出
+    pub fn use_color(&self) -> bool {
+        !self.no_color && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+    }
+}
+
+/// `unpack` 的参数
+#[derive(clap::Args, Debug)]
+pub struct UnpackArgs {
+    /// lockscreen 文件路径, 可指定多个
+    #[arg(required = true, value_name = "lockscreen文件", value_hint = clap::ValueHint::FilePath)]
+    pub input_paths: Vec<PathBuf>,
+
+    /// 输出目录路径（默认为 lockscreen 文件所在目录）
+    #[arg(short, long, value_name = "输出目录", value_hint = clap::ValueHint::DirPath)]
+    pub output: Option<PathBuf>,
+}
+
+/// `check` 的参数
+#[derive(clap::Args, Debug)]
+pub struct CheckArgs {
+    /// 锁屏包路径, 可指定多个
+    #[arg(required = true, value_name = "锁屏包路径", value_hint = clap::ValueHint::DirPath)]
+    pub input_paths: Vec<PathBuf>,
+
+    /// 在输入目录下递归查找所有锁屏包
+    #[arg(short, long, help = "递归查找输入目录下的所有锁屏包并逐个检查")]
+    pub recursive: bool,
+
+    /// 以 JSON 格式输出检查结果
+    #[arg(long)]
+    pub json: bool,
+
+    /// 额外的排除规则（gitignore 语法）, 可重复指定; 被排除的文件不参与检查
+    #[arg(long, value_name = "规则")]
+    pub exclude: Vec<String>,
+}
+
+/// `info` 的参数
+#[derive(clap::Args, Debug)]
+pub struct InfoArgs {
+    /// lockscreen 文件路径, 可指定多个
+    #[arg(required = true, value_name = "lockscreen文件", value_hint = clap::ValueHint::FilePath)]
+    pub input_paths: Vec<PathBuf>,
+
+    /// 以 JSON 格式输出锁屏包信息
+    #[arg(long)]
+    pub json: bool,
+}
+
+/// `register` 与 `unregister` 的参数
+#[derive(clap::Args, Debug)]
+pub struct RegisterArgs {
+    /// 只列出将写入或删除的注册表项或文件
+    #[arg(long)]
+    pub dry_run: bool,
+}
+
+/// `pack` 的参数
+#[derive(clap::Args, Debug)]
+pub struct PackArgs {
+    /// 锁屏包路径（必须包含 preview、description.xml、lockscreen/manifest.xml）, 可指定多个
     #[arg(value_name = "锁屏包路径", value_hint = clap::ValueHint::DirPath)]
-    pub input_path: Option<PathBuf>,
+    pub input_paths: Vec<PathBuf>,
+
+    /// 在输入目录下递归查找所有锁屏包
+    #[arg(short, long, help = "递归查找输入目录下的所有锁屏包并批量打包")]
+    pub recursive: bool,
+
+    /// 监听锁屏包目录, 文件变化时自动重新打包, 同 `vlp watch`
+    #[arg(short, long, help = "监听锁屏包变化并自动重新打包（同 vlp watch）")]
+    pub watch: bool,
 
-    /// 执行注册功能
-    #[arg(long, help = "注册右键菜单项")]
-    pub register: bool,
+    /// 批量打包时的并行任务数（默认为 CPU 核心数）
+    #[arg(short, long, value_name = "任务数")]
+    pub jobs: Option<usize>,
 
-    /// 执行注册功能
-    #[arg(long, help = "取消注册右键菜单项")]
-    pub unregister: bool,
+    /// 只校验并列出将要生成的文件与条目, 不写入任何文件
+    #[arg(
+        long,
+        conflicts_with = "watch",
+        help = "预览打包结果（编号、输出路径与各层条目）, 不写入任何文件"
+    )]
+    pub dry_run: bool,
 
-    /// 指定 zip 可执行文件路径
+    /// 跳过打包前的深度检查（仍会检查必要文件是否存在）
+    #[arg(long, help = "跳过打包前的锁屏包检查")]
+    pub no_check: bool,
+
+    /// 以 JSON 格式输出每个锁屏包的打包记录
+    #[arg(long, help = "以 JSON 格式输出打包记录")]
+    pub json: bool,
+
+    /// 将打包记录以 JSON 格式写入文件, 与 `--json` 的输出相同
+    #[arg(long, value_name = "文件", value_hint = clap::ValueHint::FilePath)]
+    pub result_file: Option<PathBuf>,
+
+    /// 压缩后端（默认使用内置压缩）
+    #[arg(long, value_enum, value_name = "后端")]
+    pub archiver: Option<ArchiverKind>,
+
+    /// 使用外部 zip 程序打包（默认使用内置压缩）
     #[arg(short, long, value_name = "ZIP可执行文件", value_hint = clap::ValueHint::FilePath)]
     pub zip_path: Option<PathBuf>,
 
     /// 输出目录路径（默认为输入路径的上一级目录）
     #[arg(short, long, value_name = "输出目录", value_hint = clap::ValueHint::DirPath)]
     pub output: Option<PathBuf>,
+
+    /// 文本等可压缩文件的 deflate 级别, 0 表示全部仅存储（默认为 6）
+    #[arg(long, value_name = "0-9", value_parser = clap::value_parser!(u8).range(0..=9))]
+    pub compression_level: Option<u8>,
+
+    /// 额外的仅存储（不压缩）扩展名, 可重复指定; 压缩包与常见图片、音视频默认仅存储
+    #[arg(long, value_name = "扩展名")]
+    pub store: Vec<String>,
+
+    /// 已有标题的处理方式: keep（保留）、id（zh_CN 标题设为锁屏编号, 默认）、id-all（所有语言的标题设为锁屏编号）、
+    /// config（只使用指定的标题）, 或如 "{title}-{id}" 的模板（支持 {title}、{id}、{locale}, 对所有语言生效）
+    #[arg(long, value_name = "策略")]
+    pub title_strategy: Option<TitleStrategy>,
+
+    /// 设置标题, 可重复指定多个语言
+    #[arg(long, value_name = "LOCALE=TEXT", value_parser = parse_localized)]
+    pub title: Vec<LocalizedText>,
+
+    /// 设置简介, 可重复指定多个语言
+    #[arg(long, value_name = "LOCALE=TEXT", value_parser = parse_localized)]
+    pub description: Vec<LocalizedText>,
+
+    /// 设置作者
+    #[arg(long, value_name = "作者")]
+    pub author: Option<String>,
+
+    /// 设置设计师
+    #[arg(long, value_name = "设计师")]
+    pub designer: Option<String>,
+
+    /// 输出文件名模板, 支持 {title}、{id}、{date}、{dirname}, 扩展名按需写入模板（默认 lockscreen）
+    #[arg(long, value_name = "模板", value_parser = parse_output_name)]
+    pub output_name: Option<String>,
+
+    /// 输出文件已存在时覆盖
+    #[arg(long, help = "输出文件已存在时覆盖")]
+    pub force: bool,
+
+    /// 输出文件已存在时报错, 不修改已有文件
+    #[arg(long, conflicts_with = "force", help = "输出文件已存在时报错")]
+    pub no_clobber: bool,
+
+    /// 输出文件已存在时在文件名后追加 -1、-2 等序号
+    #[arg(
+        long,
+        conflicts_with_all = ["force", "no_clobber"],
+        help = "输出文件已存在时自动追加序号"
+    )]
+    pub auto_suffix: bool,
+
+    /// 覆盖输出文件前将其保留为 <输出文件>.bak
+    #[arg(long, help = "覆盖输出文件前保留 .bak 备份")]
+    pub backup: bool,
+
+    /// 指定锁屏编号, 优先于 vlp.lock 与自动分配的编号
+    #[arg(long, value_name = "编号", value_parser = parse_id)]
+    pub id: Option<String>,
+
+    /// 沿用源 description.xml 中已有的编号, 并写入 vlp.lock
+    #[arg(
+        long,
+        conflicts_with = "id",
+        help = "沿用 description.xml 中已有的编号"
+    )]
+    pub keep_id: bool,
+
+    /// 忽略 vlp.lock 分配新编号, 并更新 vlp.lock
+    #[arg(
+        long,
+        conflicts_with_all = ["id", "keep_id"],
+        help = "分配新编号并更新 vlp.lock"
+    )]
+    pub new_id: bool,
+
+    /// 新编号的分配方式
+    #[arg(long, value_enum, value_name = "方式")]
+    pub id_strategy: Option<IdStrategy>,
+
+    /// 新编号的模板, 支持 {date} 与 {seq}/{seq:03}（默认 {date}{seq:03}）
+    #[arg(long, value_name = "模板")]
+    pub id_template: Option<IdTemplate>,
+
+    /// 编号登记簿路径, 可指向团队共享目录（默认为 VLP_ID_REGISTRY 或用户数据目录下的 vlp/ids.txt）
+    #[arg(long, value_name = "文件", value_hint = clap::ValueHint::FilePath)]
+    pub id_registry: Option<PathBuf>,
+
+    /// 设置锁屏版本号（-V/--version 用于显示程序版本）
+    #[arg(long, value_name = "版本号")]
+    pub theme_version: Option<String>,
+
+    /// 设置 UI 版本号
+    #[arg(long, value_name = "UI版本号")]
+    pub uiversion: Option<String>,
+
+    /// 额外的排除规则（gitignore 语法）, 可重复指定
+    #[arg(long, value_name = "规则")]
+    pub exclude: Vec<String>,
+
+    /// 可复现构建, 设置了 SOURCE_DATE_EPOCH 环境变量时自动启用
+    #[arg(long, help = "可复现构建（固定时间戳、条目顺序与锁屏编号）")]
+    pub reproducible: bool,
 }
 
-impl Args {
-    pub fn get_input_path(&self) -> Result<&Path, ArchiveError> {
-        self.input_path.as_deref().ok_or_else(|| {
-            ArchiveError::InvalidPath(io::Error::new(
+/// 未设置 SOURCE_DATE_EPOCH 时可复现构建使用的时间戳（1980-01-01T00:00:00Z）
+const DEFAULT_SOURCE_DATE_EPOCH: i64 = 315_532_800;
+
+impl PackArgs {
+    pub fn get_input_paths(&self) -> Result<&[PathBuf], ArchiveError> {
+        if self.input_paths.is_empty() {
+            return Err(ArchiveError::InvalidPath(io::Error::new(
                 io::ErrorKind::InvalidInput,
-                "未提供锁屏包路径, 请拖拽或指定锁屏包路径\n用法: vlp <输入路径>\n获取帮助信息, 尝试 'vlp --help'.",
-            ))
+                "未提供锁屏包路径, 请拖拽或指定锁屏包路径\n用法: vlp [pack] <输入路径>...\n获取帮助信息, 尝试 'vlp --help'.",
+            )));
+        }
+        Ok(&self.input_paths)
+    }
+
+    /// 待打包的锁屏包目录; 启用 `--recursive` 时为输入目录下找到的所有锁屏包
+    pub fn package_dirs(&self) -> Result<Vec<PathBuf>, ArchiveError> {
+        find_packages(self.get_input_paths()?, self.recursive)
+    }
+
+    /// 输出目录, 命令行参数优先于 vlp.toml, 都未指定时为锁屏包的上一级目录
+    pub fn output_dir(&self, input: &Path, config: &PackageConfig) -> PathBuf {
+        default_output_dir(
+            self.output.as_deref().or(config.output_dir.as_deref()),
+            input,
+        )
+    }
+
+    /// 合并命令行参数与锁屏包中的 vlp.toml, 生成打包选项
+    pub fn pack_options(
+        &self,
+        config: &PackageConfig,
+        reporter: &Arc<dyn Reporter>,
+    ) -> Result<PackOptions, ArchiveError> {
+        Ok(PackOptions {
+            // --keep-id 与 --new-id 优先于 vlp.toml 中的 id
+            id: if self.keep_id || self.new_id {
+                self.id.clone()
+            } else {
+                self.id.clone().or(config.id.clone())
+            },
+            id_mode: if self.keep_id {
+                IdMode::KeepDescription
+            } else if self.new_id {
+                IdMode::New
+            } else {
+                IdMode::Locked
+            },
+            ids: self.id_policy(config),
+            output_name: self.output_name.clone().or(config.output_name.clone()),
+            on_conflict: self
+                .on_conflict()
+                .or(config.on_conflict)
+                .unwrap_or_default(),
+            backup: self.backup || config.backup == Some(true),
+            source_date_epoch: self.source_date_epoch(config)?,
+            description: self.description_edits(config),
+            compression: self.compression(config)?,
+            exclude: config
+                .exclude
+                .iter()
+                .chain(&self.exclude)
+                .cloned()
+                .collect(),
+            reporter: reporter.clone(),
         })
     }
 
-    pub fn validate_input(&self) -> Result<(), ArchiveError> {
-        let input_path = self.get_input_path()?;
-        let required = ["preview", "description.xml", "lockscreen/manifest.xml"];
-        let mut missing = Vec::new();
+    /// 压缩后端, 命令行参数优先于 vlp.toml 中的 `[archiver]`
+    pub fn archiver(
+        &self,
+        config: &PackageConfig,
+        source_date_epoch: Option<i64>,
+    ) -> Result<Box<dyn Archiver>, ArchiveError> {
+        let kind = self
+            .archiver
+            .or(config.archiver.backend)
+            .unwrap_or_default();
+        let zip_path = self.zip_path.clone().or(match kind {
+            ArchiverKind::Zip => config.archiver.zip_path.clone(),
+            ArchiverKind::Native => None,
+        });
+        resolve_archiver(kind, zip_path.as_deref(), source_date_epoch)
+            .map_err(ArchiveError::InvalidPath)
+    }
 
-        for entry in &required {
-            let path = input_path.join(entry);
-            if !path.exists() {
-                missing.push(entry.to_string());
+    /// 可复现构建使用的固定时间戳, 未启用可复现构建时返回 None
+    pub fn source_date_epoch(&self, config: &PackageConfig) -> Result<Option<i64>, ArchiveError> {
+        match std::env::var("SOURCE_DATE_EPOCH") {
+            Ok(value) if !value.trim().is_empty() => value.trim().parse().map(Some).map_err(|_| {
+                ArchiveError::InvalidPath(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("无效的 SOURCE_DATE_EPOCH: {value:?}"),
+                ))
+            }),
+            _ if self.reproducible || config.reproducible == Some(true) => {
+                Ok(Some(DEFAULT_SOURCE_DATE_EPOCH))
             }
+            _ => Ok(None),
         }
+    }
 
-        if !missing.is_empty() {
-            return Err(ArchiveError::InvalidPath(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("锁屏包缺少以下必要文件: {}", missing.join(", ")),
-            )));
+    /// 命令行指定的输出文件冲突处理方式
+    fn on_conflict(&self) -> Option<OnConflict> {
+        if self.force {
+            Some(OnConflict::Overwrite)
+        } else if self.no_clobber {
+            Some(OnConflict::Error)
+        } else if self.auto_suffix {
+            Some(OnConflict::Suffix)
+        } else {
+            None
         }
+    }
 
-        Ok(())
+    /// 新编号的分配规则, 命令行参数优先于 vlp.toml 中的 `[numbering]`
+    pub fn id_policy(&self, config: &PackageConfig) -> IdPolicy {
+        let numbering = &config.numbering;
+        IdPolicy::new(
+            self.id_strategy.or(numbering.strategy).unwrap_or_default(),
+            self.id_template
+                .clone()
+                .or(numbering.template.clone())
+                .unwrap_or_default(),
+            self.id_registry
+                .clone()
+                .or(numbering.registry.clone())
+                .or_else(default_registry_path),
+        )
     }
 
-    pub fn resolved_output_dir(&self) -> Result<PathBuf, ArchiveError> {
-        if let Some(output) = &self.output {
-            return Ok(output.clone());
-        }
+    /// 压缩策略, 命令行中的压缩级别覆盖 vlp.toml 中的配置, 仅存储扩展名则合并
+    pub fn compression(&self, config: &PackageConfig) -> Result<CompressionPolicy, ArchiveError> {
+        let level = self
+            .compression_level
+            .or(config.compression.level)
+            .unwrap_or(DEFAULT_LEVEL);
+        let stored: Vec<String> = config
+            .compression
+            .store
+            .iter()
+            .chain(&self.store)
+            .cloned()
+            .collect();
+        Ok(CompressionPolicy::new(level, &stored)?)
+    }
 
-        let input_path = self.get_input_path()?;
+    /// description.xml 修改项, 命令行中的同名字段覆盖 vlp.toml 中的配置
+    pub fn description_edits(&self, config: &PackageConfig) -> DescriptionEdits {
+        let localized = |configured: &BTreeMap<String, String>, cli: &[LocalizedText]| {
+            let mut texts: Vec<LocalizedText> = configured
+                .iter()
+                .filter(|(locale, _)| !cli.iter().any(|text| &text.locale == *locale))
+                .map(|(locale, value)| LocalizedText {
+                    locale: locale.clone(),
+                    value: value.clone(),
+                })
+                .collect();
+            texts.extend_from_slice(cli);
+            texts
+        };
+        let configured = &config.description;
 
-        if let Some(parent) = input_path.parent() {
-            return Ok(parent.to_path_buf());
+        DescriptionEdits {
+            title_strategy: self
+                .title_strategy
+                .clone()
+                .or(configured.title_strategy.clone())
+                .unwrap_or_default(),
+            titles: localized(&configured.title, &self.title),
+            descriptions: localized(&configured.description, &self.description),
+            author: self.author.clone().or(configured.author.clone()),
+            designer: self.designer.clone().or(configured.designer.clone()),
+            version: self.theme_version.clone().or(configured.version.clone()),
+            uiversion: self.uiversion.clone().or(configured.uiversion.clone()),
         }
+    }
+}
 
-        Err(ArchiveError::InvalidPath(std::io::Error::new(
-            std::io::ErrorKind::InvalidInput,
-            format!("无法从路径 {:?} 推导父目录", self.input_path),
-        )))
+/// `inputs` 本身, 或 `recursive` 时为其下找到的所有锁屏包
+pub fn find_packages(inputs: &[PathBuf], recursive: bool) -> Result<Vec<PathBuf>, ArchiveError> {
+    if !recursive {
+        return Ok(inputs.to_vec());
+    }
+
+    let mut packages = Vec::new();
+    for root in inputs {
+        packages.extend(discover_packages(root)?);
+    }
+    if packages.is_empty() {
+        return Err(ArchiveError::InvalidPath(io::Error::new(
+            io::ErrorKind::NotFound,
+            "未在输入目录下找到任何锁屏包",
+        )));
     }
+    Ok(packages)
+}
+
+/// 未指定输出目录时使用 `input` 的上一级目录
+pub fn default_output_dir(output: Option<&Path>, input: &Path) -> PathBuf {
+    output.map_or_else(|| parent_output_dir(input), Path::to_path_buf)
+}
+
+fn parse_id(id: &str) -> Result<String, String> {
+    validate_id(id)
+        .map(|_| id.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn parse_output_name(template: &str) -> Result<String, String> {
+    validate_output_name(template)
+        .map(|_| template.to_string())
+        .map_err(|e| e.to_string())
 }
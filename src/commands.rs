@@ -0,0 +1,272 @@
+use crate::batch::{BATCH_OUTPUT_NAME, PackJob, PackResult, pack_all, print_summary};
+use crate::cli::{
+    CheckArgs, Command, GlobalArgs, InfoArgs, PackArgs, RegisterArgs, UnpackArgs,
+    default_output_dir, find_packages,
+};
+use crate::register::{do_register, do_unregister};
+use crate::watch::watch_lockscreen_package;
+use console::Emoji;
+use std::path::Path;
+use std::sync::Arc;
+use vivo_lockscreen_packing::check::check_lockscreen_package;
+use vivo_lockscreen_packing::config::PackageConfig;
+use vivo_lockscreen_packing::inspect::inspect_lockscreen_package;
+use vivo_lockscreen_packing::report::{PlainReporter, ProgressReporter, Reporter, SilentReporter};
+use vivo_lockscreen_packing::unpack::unpack_lockscreen_package;
+use vivo_lockscreen_packing::{ArchiveError, BuildRecord};
+
+static SPARKLE: Emoji<'_, '_> = Emoji("✨ ", ":-)");
+static SUCCESS: Emoji<'_, '_> = Emoji("✅ ", "OK ");
+static FAILURE: Emoji<'_, '_> = Emoji("❌ ", "ERR");
+
+/// 各子命令共用的运行环境: 全局参数与进度输出方式,
+/// 并负责为每个锁屏包读取 vlp.toml、解析压缩后端
+pub struct Context {
+    pub global: GlobalArgs,
+    pub reporter: Arc<dyn Reporter>,
+}
+
+impl Context {
+    /// 进度输出方式: `--quiet` 或 JSON 输出时不输出, 标准输出不是终端时逐行输出, 否则显示进度条
+    pub fn new(global: GlobalArgs, json: bool) -> Self {
+        if !global.use_color() {
+            console::set_colors_enabled(false);
+            console::set_colors_enabled_stderr(false);
+        }
+
+        let reporter: Arc<dyn Reporter> = if global.quiet || json {
+            Arc::new(SilentReporter)
+        } else if console::Term::stdout().is_term() {
+            Arc::new(ProgressReporter::new(global.verbose))
+        } else {
+            Arc::new(PlainReporter::new(global.verbose))
+        };
+        Context { global, reporter }
+    }
+
+    /// 合并命令行参数与锁屏包中的 vlp.toml, 生成打包任务
+    pub fn pack_job(&self, args: &PackArgs, input: &Path) -> Result<PackJob, ArchiveError> {
+        let config = PackageConfig::load(input)?;
+        let options = args.pack_options(&config, &self.reporter)?;
+        let archiver = args.archiver(&config, options.source_date_epoch)?;
+
+        Ok(PackJob {
+            input: input.to_path_buf(),
+            output_dir: args.output_dir(input, &config),
+            archiver,
+            options,
+            check: !args.no_check,
+        })
+    }
+
+    /// 执行子命令
+    pub fn run(&self, command: &Command) -> Result<(), ArchiveError> {
+        match command {
+            Command::Pack(args) if args.watch => self.watch(args),
+            Command::Pack(args) => self.pack(args),
+            Command::Watch(args) => self.watch(args),
+            Command::Unpack(args) => self.unpack(args),
+            Command::Check(args) => self.check(args),
+            Command::Info(args) => self.info(args),
+            Command::Register(args) => self.register(args, true),
+            Command::Unregister(args) => self.register(args, false),
+        }
+    }
+
+    fn pack(&self, args: &PackArgs) -> Result<(), ArchiveError> {
+        let packages = args.package_dirs()?;
+        let single = packages.len() == 1 && !args.recursive;
+        let make_job = |input: &Path| {
+            let mut job = self.pack_job(args, input)?;
+            if !single {
+                job.options
+                    .output_name
+                    .get_or_insert_with(|| BATCH_OUTPUT_NAME.to_string());
+            }
+            Ok(job)
+        };
+
+        if args.dry_run {
+            let mut plans = Vec::new();
+            for input in &packages {
+                plans.push(make_job(input)?.plan()?);
+            }
+            if args.json {
+                let json = serde_json::to_string_pretty(&plans).map_err(std::io::Error::other)?;
+                println!("{json}");
+            } else {
+                for plan in &plans {
+                    println!("{plan}");
+                }
+            }
+            return Ok(());
+        }
+
+        let mut results = if single {
+            let input = &packages[0];
+            vec![PackResult {
+                input: input.clone(),
+                result: make_job(input).and_then(|job| job.run()),
+            }]
+        } else {
+            let workers = args.jobs.unwrap_or_else(|| {
+                std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+            });
+            pack_all(&packages, workers, make_job)
+        };
+        write_records(args, &results)?;
+
+        // 单个锁屏包保留原始错误及其退出码
+        if single && let Some(PackResult { result, .. }) = results.pop() {
+            return result.map(drop);
+        }
+
+        let failed = results.iter().filter(|r| r.result.is_err()).count();
+        if !args.json && (!self.global.quiet || failed > 0) {
+            print_summary(&results);
+        }
+
+        if failed > 0 {
+            return Err(ArchiveError::BatchFailed {
+                failed,
+                total: results.len(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// 监听单个锁屏包, 直到进程被终止
+    fn watch(&self, args: &PackArgs) -> Result<(), ArchiveError> {
+        let invalid = |message: &str| {
+            ArchiveError::InvalidPath(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                message.to_string(),
+            ))
+        };
+        if args.dry_run {
+            return Err(invalid("监听模式不支持 --dry-run"));
+        }
+        let packages = args.package_dirs()?;
+        let [input] = packages.as_slice() else {
+            return Err(invalid("监听模式只能指定一个锁屏包"));
+        };
+        watch_lockscreen_package(self.pack_job(args, input)?)
+    }
+
+    fn unpack(&self, args: &UnpackArgs) -> Result<(), ArchiveError> {
+        for input in &args.input_paths {
+            let output_dir = default_output_dir(args.output.as_deref(), input);
+            let target = unpack_lockscreen_package(input, &output_dir)?;
+            if !self.global.quiet {
+                println!("{SPARKLE}输出路径: {}", target.display());
+            }
+        }
+        Ok(())
+    }
+
+    /// 逐个检查锁屏包; 单个锁屏包未通过时返回其检查错误, 多个时先输出各自的问题再汇总
+    fn check(&self, args: &CheckArgs) -> Result<(), ArchiveError> {
+        let packages = find_packages(&args.input_paths, args.recursive)?;
+        let mut reports = Vec::new();
+        // 无法完成检查（如 vlp.toml 无效）的锁屏包同样计为失败, 不中断其余锁屏包
+        let mut failed = Vec::new();
+        for input in &packages {
+            let result = PackageConfig::load(input).and_then(|config| {
+                let exclude: Vec<String> = config
+                    .exclude
+                    .iter()
+                    .chain(&args.exclude)
+                    .cloned()
+                    .collect();
+                check_lockscreen_package(input, &exclude)
+            });
+            match result {
+                Ok(report) => reports.push(report),
+                Err(e) => failed.push(e),
+            }
+        }
+
+        if args.json {
+            let json = serde_json::to_string_pretty(&reports).map_err(std::io::Error::other)?;
+            println!("{json}");
+        } else if !self.global.quiet {
+            for report in reports.iter().filter(|report| report.is_ok()) {
+                print!("{SUCCESS}{report}");
+            }
+        }
+
+        let total = packages.len();
+        failed.extend(
+            reports
+                .into_iter()
+                .filter_map(|report| report.into_result().err()),
+        );
+        match failed.len() {
+            0 => Ok(()),
+            1 if total == 1 => Err(failed.remove(0)),
+            count => {
+                if !args.json {
+                    for err in &failed {
+                        eprintln!("{FAILURE}{err}");
+                    }
+                }
+                Err(ArchiveError::BatchFailed {
+                    failed: count,
+                    total,
+                })
+            }
+        }
+    }
+
+    fn info(&self, args: &InfoArgs) -> Result<(), ArchiveError> {
+        for input in &args.input_paths {
+            let info = inspect_lockscreen_package(input)?;
+            if args.json {
+                let json = serde_json::to_string_pretty(&info).map_err(std::io::Error::other)?;
+                println!("{json}");
+            } else {
+                print!("{info}");
+            }
+        }
+        Ok(())
+    }
+
+    fn register(&self, args: &RegisterArgs, register: bool) -> Result<(), ArchiveError> {
+        let files = if register {
+            do_register(args.dry_run)?
+        } else {
+            do_unregister(args.dry_run)?
+        };
+        match (args.dry_run, register) {
+            (true, true) => println!("注册右键功能时将写入:"),
+            (true, false) => println!("取消注册右键功能时将删除:"),
+            (false, true) => println!("{SPARKLE}右键功能注册成功! 已写入:"),
+            (false, false) => println!("{SPARKLE}右键功能取消注册成功! 已删除:"),
+        }
+        for file in files {
+            println!("  {file}");
+        }
+        Ok(())
+    }
+}
+
+/// 按 `--json` 与 `--result-file` 输出每个锁屏包的打包记录
+fn write_records(args: &PackArgs, results: &[PackResult]) -> Result<(), ArchiveError> {
+    if !args.json && args.result_file.is_none() {
+        return Ok(());
+    }
+
+    let records: Vec<BuildRecord> = results
+        .iter()
+        .map(|r| BuildRecord::new(&r.input, &r.result))
+        .collect();
+    let json = serde_json::to_string_pretty(&records).map_err(std::io::Error::other)?;
+    if args.json {
+        println!("{json}");
+    }
+    if let Some(path) = &args.result_file {
+        std::fs::write(path, format!("{json}\n")).map_err(|e| ArchiveError::io_at(path, e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use flate2::Compression;
+use flate2::write::DeflateEncoder;
+use zip::CompressionMethod;
+use zip::write::SimpleFileOptions;
+
+/// 默认的 deflate 压缩级别, 与 zip 命令一致
+pub const DEFAULT_LEVEL: u8 = 6;
+
+/// 小于此大小的条目先在内存中试压缩, deflate 后不变小时改为仅存储（与 zip 命令一致）
+pub const SMALL_ENTRY_SIZE: u64 = 64 * 1024;
+
+/// 内置的仅存储扩展名: 压缩包（包括嵌套的 lockscreen/<id>.zip 与 .itz）以及已压缩的图片、音视频
+const STORED_EXTENSIONS: &[&str] = &[
+    "zip", "itz", "jar", "apk", "gz", "7z", "rar", // 压缩包
+    "png", "jpg", "jpeg", "webp", "gif", // 图片
+    "mp4", "webm", "mkv", "3gp", "mp3", "ogg", "m4a", "aac", // 音视频
+];
+
+/// 单个条目的压缩策略: 已压缩的数据仅存储, 其余文件（如 XML）按指定级别 deflate
+#[derive(Debug, Clone)]
+pub struct CompressionPolicy {
+    /// deflate 级别 (0-9), 0 表示所有条目都仅存储
+    level: u8,
+    /// 额外的仅存储扩展名（小写, 不含 `.`）
+    stored: Vec<String>,
+}
+
+impl CompressionPolicy {
+    /// `stored` 为内置列表之外的仅存储扩展名, 可带或不带前导 `.`
+    pub fn new(level: u8, stored: &[String]) -> io::Result<Self> {
+        if level > 9 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("无效的压缩级别: {level}, 应为 0-9"),
+            ));
+        }
+
+        Ok(CompressionPolicy {
+            level,
+            stored: stored
+                .iter()
+                .map(|ext| ext.trim_start_matches('.').to_ascii_lowercase())
+                .collect(),
+        })
+    }
+
+    /// 条目 `path` 是否仅存储
+    pub fn is_stored(&self, path: &Path) -> bool {
+        if self.level == 0 {
+            return true;
+        }
+        let Some(ext) = path.extension() else {
+            return false;
+        };
+        let ext = ext.to_string_lossy().to_ascii_lowercase();
+        STORED_EXTENSIONS.contains(&ext.as_str()) || self.stored.contains(&ext)
+    }
+
+    pub fn level(&self) -> u8 {
+        self.level
+    }
+
+    /// 全部仅存储扩展名, 供外部 zip 程序的 `-n` 参数使用
+    pub fn stored_extensions(&self) -> impl Iterator<Item = &str> {
+        STORED_EXTENSIONS
+            .iter()
+            .copied()
+            .chain(self.stored.iter().map(String::as_str))
+    }
+
+    /// 为条目 `path` 设置压缩方式与级别
+    pub fn apply(&self, path: &Path, options: SimpleFileOptions) -> SimpleFileOptions {
+        if self.is_stored(path) {
+            options.compression_method(CompressionMethod::Stored)
+        } else {
+            options
+                .compression_method(CompressionMethod::Deflated)
+                .compression_level(Some(self.level.into()))
+        }
+    }
+
+    /// 与 [`apply`](Self::apply) 相同, 但 `content` deflate 后不比原文小时仅存储
+    ///
+    /// 需要在内存中完整压缩一次, 只用于小于 [`SMALL_ENTRY_SIZE`] 的条目。
+    pub fn apply_to_content(
+        &self,
+        path: &Path,
+        content: &[u8],
+        options: SimpleFileOptions,
+    ) -> SimpleFileOptions {
+        if !self.is_stored(path) && self.deflated_size(content) >= content.len() {
+            return options.compression_method(CompressionMethod::Stored);
+        }
+        self.apply(path, options)
+    }
+
+    fn deflated_size(&self, content: &[u8]) -> usize {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::new(self.level.into()));
+        encoder
+            .write_all(content)
+            .and_then(|_| encoder.finish())
+            .map_or(usize::MAX, |deflated| deflated.len())
+    }
+}
+
+impl Default for CompressionPolicy {
+    fn default() -> Self {
+        CompressionPolicy {
+            level: DEFAULT_LEVEL,
+            stored: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn method(content: &[u8], name: &str) -> CompressionMethod {
+        let options = CompressionPolicy::default().apply_to_content(
+            Path::new(name),
+            content,
+            SimpleFileOptions::default(),
+        );
+        let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.start_file(name, options).unwrap();
+        writer.write_all(content).unwrap();
+        let mut archive = zip::ZipArchive::new(writer.finish().unwrap()).unwrap();
+        archive.by_index(0).unwrap().compression()
+    }
+
+    #[test]
+    fn entries_that_do_not_shrink_are_stored() {
+        assert_eq!(
+            method(b"<Lockscreen/>\n", "manifest.xml"),
+            CompressionMethod::Stored
+        );
+        assert_eq!(method(b"", "empty.xml"), CompressionMethod::Stored);
+        assert_eq!(
+            method("<Text/>\n".repeat(64).as_bytes(), "manifest.xml"),
+            CompressionMethod::Deflated
+        );
+        assert_eq!(method(&[0; 1024], "bg.png"), CompressionMethod::Stored);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::archiver::ArchiverKind;
+use crate::description::TitleStrategy;
+use crate::error::ArchiveError;
+use crate::id::{IdStrategy, IdTemplate};
+use crate::output::{OnConflict, validate_output_name};
+use crate::utils::validate_id;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 锁屏包根目录下（与 description.xml 同级）的配置文件名
+pub const CONFIG_FILE_NAME: &str = "vlp.toml";
+
+/// `vlp.toml` 中的打包配置, 命令行参数优先于配置文件
+///
+/// 配置中的相对路径均相对于锁屏包根目录。
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PackageConfig {
+    /// 输出目录
+    pub output_dir: Option<PathBuf>,
+    /// 输出文件名模板, 如 `"{dirname}-{id}.itz"`
+    pub output_name: Option<String>,
+    /// 输出文件已存在时的处理方式
+    pub on_conflict: Option<OnConflict>,
+    /// 覆盖输出文件前保留 .bak 备份
+    pub backup: Option<bool>,
+    /// 固定使用的锁屏编号
+    pub id: Option<String>,
+    /// 是否进行可复现构建
+    pub reproducible: Option<bool>,
+    /// 额外的排除规则（gitignore 语法）, 与内置规则及 .vlpignore 合并
+    pub exclude: Vec<String>,
+    pub archiver: ArchiverConfig,
+    pub compression: CompressionConfig,
+    pub numbering: NumberingConfig,
+    pub description: DescriptionConfig,
+}
+
+/// `[archiver]`: 压缩后端
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiverConfig {
+    pub backend: Option<ArchiverKind>,
+    /// 外部 zip 程序路径, 仅在 backend = "zip" 时生效
+    pub zip_path: Option<PathBuf>,
+}
+
+/// `[compression]`: 压缩策略
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    /// deflate 级别 (0-9)
+    pub level: Option<u8>,
+    /// 额外的仅存储扩展名, 如 `store = ["ttf"]`
+    pub store: Vec<String>,
+}
+
+/// `[numbering]`: 新锁屏编号的分配规则
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NumberingConfig {
+    pub strategy: Option<IdStrategy>,
+    /// 编号模板, 如 `"{date}{seq:03}"`
+    pub template: Option<IdTemplate>,
+    /// 编号登记簿路径, 可指向团队共享目录
+    pub registry: Option<PathBuf>,
+}
+
+/// `[description]`: 写入 description.xml 的字段
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DescriptionConfig {
+    /// 已有标题的处理方式, 如 `"keep"` 或 `"{title}-{id}"`
+    pub title_strategy: Option<TitleStrategy>,
+    /// 各语言标题, 如 `title = { zh_CN = "标题", en_US = "Title" }`
+    pub title: BTreeMap<String, String>,
+    /// 各语言简介
+    pub description: BTreeMap<String, String>,
+    pub author: Option<String>,
+    pub designer: Option<String>,
+    pub version: Option<String>,
+    pub uiversion: Option<String>,
+}
+
+impl PackageConfig {
+    /// 读取 `package_dir` 下的 `vlp.toml`, 文件不存在时返回默认配置
+    pub fn load<P: AsRef<Path>>(package_dir: P) -> Result<Self, ArchiveError> {
+        let package_dir = package_dir.as_ref();
+        let path = package_dir.join(CONFIG_FILE_NAME);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut config: PackageConfig = toml::from_str(&content).map_err(|e| {
+            ArchiveError::InvalidPath(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} 格式错误: {e}", path.display()),
+            ))
+        })?;
+
+        if let Some(template) = &config.output_name {
+            validate_output_name(template).map_err(|e| {
+                ArchiveError::InvalidPath(io::Error::new(
+                    e.kind(),
+                    format!("{} 格式错误: {e}", path.display()),
+                ))
+            })?;
+        }
+
+        if let Some(id) = &config.id {
+            validate_id(id).map_err(|e| {
+                ArchiveError::InvalidPath(io::Error::new(
+                    e.kind(),
+                    format!("{} 格式错误: {e}", path.display()),
+                ))
+            })?;
+        }
+
+        config.output_dir = config.output_dir.map(|dir| package_dir.join(dir));
+        config.archiver.zip_path = config.archiver.zip_path.map(|zip| package_dir.join(zip));
+        config.numbering.registry = config
+            .numbering
+            .registry
+            .map(|registry| package_dir.join(registry));
+        Ok(config)
+    }
+}
@@ -0,0 +1,800 @@
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+const BOM: char = '\u{feff}';
+
+/// description.xml 根元素下的一个字段, 如 `<title locale="zh_CN">...</title>`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DescriptionField {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locale: Option<String>,
+    pub value: String,
+}
+
+/// 字段在原文中的位置, 用于原样保留其余内容的情况下替换字段值
+struct FieldSpan {
+    field: DescriptionField,
+    /// 开始标签 `<` 的字节位置
+    start: usize,
+    /// 元素内容的字节范围; 对于 `<id/>` 这样的空元素为整个标签的范围
+    range: Range<usize>,
+    empty: bool,
+    cdata: bool,
+}
+
+/// 读取 description.xml 根元素下的所有字段（文本与 CDATA 内容均会被读取）
+pub fn read_fields(content: &str) -> Result<Vec<DescriptionField>, quick_xml::Error> {
+    Ok(scan_fields(content.trim_start_matches(BOM))?
+        .into_iter()
+        .map(|span| span.field)
+        .collect())
+}
+
+/// 读取根元素下的所有字段及其开始标签在 `content` 中的字节位置, 用于报告行号
+pub(crate) fn read_field_positions(
+    content: &str,
+) -> Result<Vec<(DescriptionField, usize)>, quick_xml::Error> {
+    let body = content.trim_start_matches(BOM);
+    let offset = content.len() - body.len();
+    Ok(scan_fields(body)?
+        .into_iter()
+        .map(|span| (span.field, offset + span.start))
+        .collect())
+}
+
+/// 读取 description.xml 中的 ID 和 zh_CN 标题
+pub fn read_id_and_title(content: &str) -> (Option<String>, Option<String>) {
+    let fields = read_fields(content).unwrap_or_default();
+    let find = |predicate: &dyn Fn(&DescriptionField) -> bool| {
+        fields
+            .iter()
+            .find(|field| predicate(field))
+            .map(|field| field.value.clone())
+    };
+
+    (
+        find(&|field| field.name == "id"),
+        find(&|field| field.name == "title" && field.locale.as_deref() == Some("zh_CN")),
+    )
+}
+
+/// 逐个检查根元素下的字段, `replace` 返回 Some 时替换该字段的值
+///
+/// 只改动被替换字段的内容, 注释、缩进、换行符、XML 声明与 BOM 均原样保留。
+/// 原值使用 CDATA 时新值同样写入 CDATA, 否则按 XML 文本转义。
+pub fn rewrite_fields<F>(content: &str, mut replace: F) -> Result<String, quick_xml::Error>
+where
+    F: FnMut(&DescriptionField) -> Option<String>,
+{
+    let (bom, body) = match content.strip_prefix(BOM) {
+        Some(body) => (&content[..BOM.len_utf8()], body),
+        None => ("", content),
+    };
+
+    let mut result = String::with_capacity(content.len());
+    result.push_str(bom);
+    let mut last = 0;
+
+    for span in scan_fields(body)? {
+        let Some(new_value) = replace(&span.field) else {
+            continue;
+        };
+
+        let encoded = if span.cdata && !new_value.contains("]]>") {
+            format!("<![CDATA[{new_value}]]>")
+        } else {
+            escape(new_value.as_str()).into_owned()
+        };
+
+        result.push_str(&body[last..span.range.start]);
+        if span.empty {
+            let name = &span.field.name;
+            let tag = &body[span.range.clone()];
+            let open = tag.trim_end_matches('>').trim_end_matches('/').trim_end();
+            result.push_str(&format!("{open}>{encoded}</{name}>"));
+        } else {
+            result.push_str(&encoded);
+        }
+        last = span.range.end;
+    }
+
+    result.push_str(&body[last..]);
+    Ok(result)
+}
+
+/// 按语言区分的文本, 如 `<title locale="en_US">`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LocalizedText {
+    pub locale: String,
+    pub value: String,
+}
+
+/// description.xml 的类型化模型
+///
+/// 只描述打包流程关心的字段, 其余元素在 [`Description::render`] 时原样保留。
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Description {
+    pub id: Option<String>,
+    pub titles: Vec<LocalizedText>,
+    pub descriptions: Vec<LocalizedText>,
+    pub author: Option<String>,
+    pub designer: Option<String>,
+    pub version: Option<String>,
+    pub uiversion: Option<String>,
+}
+
+impl Description {
+    pub fn parse(content: &str) -> io::Result<Self> {
+        let fields = read_fields(content).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("description.xml 解析失败: {e}"),
+            )
+        })?;
+
+        let mut description = Description::default();
+        for field in fields {
+            let value = field.value;
+            match (field.name.as_str(), field.locale) {
+                ("id", _) if description.id.is_none() => description.id = Some(value),
+                ("title", Some(locale)) => set_localized(&mut description.titles, &locale, &value),
+                ("description", Some(locale)) => {
+                    set_localized(&mut description.descriptions, &locale, &value)
+                }
+                ("author", _) => description.author = Some(value),
+                ("designer", _) => description.designer = Some(value),
+                ("version", _) => description.version = Some(value),
+                ("uiversion", _) => description.uiversion = Some(value),
+                _ => {}
+            }
+        }
+
+        Ok(description)
+    }
+
+    /// 检查必需字段, 一次性列出所有缺失或为空的元素
+    pub fn validate(&self) -> io::Result<()> {
+        let mut problems = Vec::new();
+
+        if self.id.as_deref().is_none_or(|id| id.trim().is_empty()) {
+            problems.push("<id>".to_string());
+        }
+        if self.title("zh_CN").is_none() {
+            problems.push(r#"<title locale="zh_CN">"#.to_string());
+        }
+        for text in self.titles.iter().chain(&self.descriptions) {
+            if text.locale.trim().is_empty() {
+                problems.push(format!("locale 为空的条目: {}", text.value));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("description.xml 缺少元素: {}", problems.join(", ")),
+            ))
+        }
+    }
+
+    pub fn title(&self, locale: &str) -> Option<&str> {
+        self.titles
+            .iter()
+            .find(|text| text.locale == locale)
+            .map(|text| text.value.as_str())
+    }
+
+    pub fn set_title(&mut self, locale: &str, value: &str) {
+        set_localized(&mut self.titles, locale, value);
+    }
+
+    pub fn set_description(&mut self, locale: &str, value: &str) {
+        set_localized(&mut self.descriptions, locale, value);
+    }
+
+    /// 将模型写回 `original` 文档
+    ///
+    /// 已有元素就地替换内容, 文档中不存在的字段追加到根元素末尾, 沿用原文的缩进与换行符。
+    pub fn render(&self, original: &str) -> io::Result<String> {
+        let parse_error = |e: quick_xml::Error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("description.xml 解析失败: {e}"),
+            )
+        };
+
+        let mut written_id = false;
+        let mut written_titles = Vec::new();
+        let mut written_descriptions = Vec::new();
+        let mut written_simple = Vec::new();
+
+        // 未改动的字段保留原文, 不会因写回去掉首尾空白后的值而丢失多行排版
+        let replaced = rewrite_fields(original, |field| {
+            let locale = field.locale.as_deref();
+            let value = match (field.name.as_str(), locale) {
+                ("id", _) if !written_id => {
+                    written_id = true;
+                    self.id.clone()
+                }
+                ("title", Some(locale)) => {
+                    written_titles.push(locale.to_string());
+                    localized(&self.titles, locale)
+                }
+                ("description", Some(locale)) => {
+                    written_descriptions.push(locale.to_string());
+                    localized(&self.descriptions, locale)
+                }
+                (name, _) => {
+                    let value = self.simple_field(name)?;
+                    written_simple.push(name.to_string());
+                    Some(value.to_string())
+                }
+            };
+            value.filter(|value| *value != field.value)
+        })
+        .map_err(parse_error)?;
+
+        let mut missing = Vec::new();
+        if let Some(id) = self.id.as_deref().filter(|_| !written_id) {
+            missing.push(format!("<id>{}</id>", escape(id)));
+        }
+        for text in self
+            .titles
+            .iter()
+            .filter(|t| !written_titles.contains(&t.locale))
+        {
+            missing.push(localized_element("title", text));
+        }
+        for text in self
+            .descriptions
+            .iter()
+            .filter(|t| !written_descriptions.contains(&t.locale))
+        {
+            missing.push(localized_element("description", text));
+        }
+        for name in SIMPLE_FIELDS {
+            if let Some(value) = self.simple_field(name)
+                && !written_simple.iter().any(|written| written == name)
+            {
+                missing.push(format!("<{name}>{}</{name}>", escape(value)));
+            }
+        }
+
+        if missing.is_empty() {
+            return Ok(replaced);
+        }
+        insert_fields(&replaced, &missing)
+    }
+
+    fn simple_field(&self, name: &str) -> Option<&str> {
+        match name {
+            "author" => self.author.as_deref(),
+            "designer" => self.designer.as_deref(),
+            "version" => self.version.as_deref(),
+            "uiversion" => self.uiversion.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+const SIMPLE_FIELDS: [&str; 4] = ["author", "designer", "version", "uiversion"];
+
+/// Step 2 中已有标题的处理方式
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum TitleStrategy {
+    /// 保留原标题
+    Keep,
+    /// 将 zh_CN 标题设为锁屏编号, 其余语言保留原标题
+    #[default]
+    Id,
+    /// 将所有语言的标题设为锁屏编号
+    IdAll,
+    /// 只使用配置文件或 `--title` 指定的标题, 未指定任何标题时报错
+    Config,
+    /// 按模板生成, 支持 `{title}`（该语言的原标题）、`{id}` 与 `{locale}`
+    Template(String),
+}
+
+impl TitleStrategy {
+    /// 按策略改写 `description` 中的标题, 之后再应用 [`DescriptionEdits`] 中指定的标题
+    pub fn apply(&self, description: &mut Description, id: &str) {
+        for text in &mut description.titles {
+            match self {
+                TitleStrategy::Keep | TitleStrategy::Config => {}
+                TitleStrategy::Id if text.locale != "zh_CN" => {}
+                TitleStrategy::Id | TitleStrategy::IdAll => text.value = id.to_string(),
+                TitleStrategy::Template(template) => {
+                    text.value = render_title(template, &text.value, id, &text.locale)
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for TitleStrategy {
+    type Err = io::Error;
+
+    /// `keep`、`id`、`id-all`、`config`, 含有 `{` 的值视为模板
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "keep" => Ok(TitleStrategy::Keep),
+            "id" => Ok(TitleStrategy::Id),
+            "id-all" => Ok(TitleStrategy::IdAll),
+            "config" => Ok(TitleStrategy::Config),
+            template if template.contains('{') => {
+                let mut rest = template;
+                while let Some(start) = rest.find('{') {
+                    let end = rest[start..].find('}').ok_or_else(|| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("无效的标题模板 {template:?}: 缺少 '}}'"),
+                        )
+                    })?;
+                    let placeholder = &rest[start + 1..start + end];
+                    if !TITLE_PLACEHOLDERS.contains(&placeholder) {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("无效的标题模板 {template:?}: 未知的占位符 {{{placeholder}}}"),
+                        ));
+                    }
+                    rest = &rest[start + end + 1..];
+                }
+                Ok(TitleStrategy::Template(template.to_string()))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "无效的标题策略: {value}, 应为 keep、id、id-all、config 或含有 {{title}}/{{id}} 的模板"
+                ),
+            )),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for TitleStrategy {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+const TITLE_PLACEHOLDERS: [&str; 3] = ["title", "id", "locale"];
+
+/// 替换标题模板中的占位符, 替换后的文本不再解析
+fn render_title(template: &str, title: &str, id: &str, locale: &str) -> String {
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        match &rest[start + 1..start + end] {
+            "title" => result.push_str(title),
+            "id" => result.push_str(id),
+            "locale" => result.push_str(locale),
+            _ => result.push_str(&rest[start..=start + end]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    result
+}
+
+/// 命令行指定的 description.xml 修改项, 在 Step 2 中应用
+#[derive(Debug, Clone, Default)]
+pub struct DescriptionEdits {
+    /// 已有标题的处理方式, 先于 `titles` 应用
+    pub title_strategy: TitleStrategy,
+    pub titles: Vec<LocalizedText>,
+    pub descriptions: Vec<LocalizedText>,
+    pub author: Option<String>,
+    pub designer: Option<String>,
+    pub version: Option<String>,
+    pub uiversion: Option<String>,
+}
+
+impl DescriptionEdits {
+    pub fn apply_to(&self, description: &mut Description) {
+        for text in &self.titles {
+            description.set_title(&text.locale, &text.value);
+        }
+        for text in &self.descriptions {
+            description.set_description(&text.locale, &text.value);
+        }
+        let fields = [
+            (&self.author, &mut description.author),
+            (&self.designer, &mut description.designer),
+            (&self.version, &mut description.version),
+            (&self.uiversion, &mut description.uiversion),
+        ];
+        for (edit, field) in fields {
+            if let Some(value) = edit {
+                *field = Some(value.clone());
+            }
+        }
+    }
+}
+
+/// 解析 `LOCALE=TEXT` 形式的命令行参数
+pub fn parse_localized(arg: &str) -> Result<LocalizedText, String> {
+    match arg.split_once('=') {
+        Some((locale, value)) if !locale.trim().is_empty() => Ok(LocalizedText {
+            locale: locale.trim().to_string(),
+            value: value.to_string(),
+        }),
+        _ => Err(format!("格式应为 LOCALE=TEXT, 如 zh_CN=标题: {arg}")),
+    }
+}
+
+fn set_localized(texts: &mut Vec<LocalizedText>, locale: &str, value: &str) {
+    match texts.iter_mut().find(|text| text.locale == locale) {
+        Some(text) => text.value = value.to_string(),
+        None => texts.push(LocalizedText {
+            locale: locale.to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+fn localized(texts: &[LocalizedText], locale: &str) -> Option<String> {
+    texts
+        .iter()
+        .find(|text| text.locale == locale)
+        .map(|text| text.value.clone())
+}
+
+fn localized_element(name: &str, text: &LocalizedText) -> String {
+    let value = if text.value.contains("]]>") {
+        escape(text.value.as_str()).into_owned()
+    } else {
+        format!("<![CDATA[{}]]>", text.value)
+    };
+    format!(
+        r#"<{name} locale="{}">{value}</{name}>"#,
+        escape(text.locale.as_str())
+    )
+}
+
+/// 在根元素结束标签前插入新元素, 缩进与换行符沿用最后一个字段
+fn insert_fields(content: &str, elements: &[String]) -> io::Result<String> {
+    let (bom, body) = match content.strip_prefix(BOM) {
+        Some(body) => (&content[..BOM.len_utf8()], body),
+        None => ("", content),
+    };
+    let document = scan_document(body).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("description.xml 解析失败: {e}"),
+        )
+    })?;
+    let root_end = document
+        .root_end
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "description.xml 缺少根元素"))?;
+
+    let newline = if body.contains("\r\n") { "\r\n" } else { "\n" };
+    let indent = document
+        .spans
+        .last()
+        .map(|span| {
+            let line_start = body[..span.start].rfind('\n').map_or(0, |i| i + 1);
+            &body[line_start..span.start]
+        })
+        .filter(|prefix| prefix.trim().is_empty())
+        .unwrap_or("    ");
+
+    let line_start = body[..root_end].rfind('\n').map_or(0, |i| i + 1);
+    let mut inserted = String::new();
+    let insert_at = if line_start > 0 && body[line_start..root_end].trim().is_empty() {
+        for element in elements {
+            inserted.push_str(&format!("{indent}{element}{newline}"));
+        }
+        line_start
+    } else {
+        for element in elements {
+            inserted.push_str(&format!("{newline}{indent}{element}"));
+        }
+        inserted.push_str(newline);
+        root_end
+    };
+
+    Ok(format!(
+        "{bom}{}{inserted}{}",
+        &body[..insert_at],
+        &body[insert_at..]
+    ))
+}
+
+/// 按 Step 2 的规则修改 description.xml: 写入新编号, 按 `edits.title_strategy` 改写标题, 再应用 `edits`
+///
+/// 源文件缺少 `<id>` 或 zh_CN `<title>` 时返回具体的错误。
+pub fn patch_description<P: AsRef<Path>>(
+    description_file: P,
+    new_id: &str,
+    edits: &DescriptionEdits,
+) -> io::Result<()> {
+    let path = description_file.as_ref();
+    let content = fs::read_to_string(path)?;
+    fs::write(path, patched_description(&content, new_id, edits)?)
+}
+
+/// [`patch_description`] 的内存版本, 返回修改后的文档
+pub fn patched_description(
+    content: &str,
+    new_id: &str,
+    edits: &DescriptionEdits,
+) -> io::Result<String> {
+    let mut description = Description::parse(content)?;
+    description.validate()?;
+
+    if edits.title_strategy == TitleStrategy::Config && edits.titles.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "标题策略为 config, 但 vlp.toml 与 --title 均未指定标题",
+        ));
+    }
+
+    description.id = Some(new_id.to_string());
+    edits.title_strategy.apply(&mut description, new_id);
+    edits.apply_to(&mut description);
+    description.validate()?;
+
+    description.render(content)
+}
+
+/// 扫描结果: 根元素下的字段及根元素结束标签的位置
+struct ScannedDocument {
+    spans: Vec<FieldSpan>,
+    root_end: Option<usize>,
+}
+
+fn scan_fields(content: &str) -> Result<Vec<FieldSpan>, quick_xml::Error> {
+    Ok(scan_document(content)?.spans)
+}
+
+fn scan_document(content: &str) -> Result<ScannedDocument, quick_xml::Error> {
+    let mut reader = Reader::from_str(content);
+    let mut spans = Vec::new();
+    let mut root_end = None;
+    let mut depth = 0usize;
+    let mut current: Option<FieldSpan> = None;
+
+    loop {
+        let before = reader.buffer_position() as usize;
+        match reader.read_event()? {
+            Event::Start(start) => {
+                depth += 1;
+                if depth == 2 {
+                    let after = reader.buffer_position() as usize;
+                    current = Some(FieldSpan {
+                        field: new_field(&start)?,
+                        start: before,
+                        range: after..after,
+                        empty: false,
+                        cdata: false,
+                    });
+                }
+            }
+            Event::Empty(empty) if depth == 1 => {
+                let after = reader.buffer_position() as usize;
+                spans.push(FieldSpan {
+                    field: new_field(&empty)?,
+                    start: before,
+                    range: before..after,
+                    empty: true,
+                    cdata: false,
+                });
+            }
+            Event::Text(text) => {
+                if let Some(span) = current.as_mut() {
+                    span.field.value.push_str(&text.unescape()?);
+                }
+            }
+            Event::CData(cdata) => {
+                if let Some(span) = current.as_mut() {
+                    span.field.value.push_str(&cdata.decode()?);
+                    span.cdata = true;
+                }
+            }
+            Event::End(_) => {
+                if depth == 2
+                    && let Some(mut span) = current.take()
+                {
+                    span.range.end = before;
+                    span.field.value = span.field.value.trim().to_string();
+                    spans.push(span);
+                } else if depth == 1 {
+                    root_end = Some(before);
+                }
+                depth = depth.saturating_sub(1);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(ScannedDocument { spans, root_end })
+}
+
+fn new_field(start: &BytesStart<'_>) -> Result<DescriptionField, quick_xml::Error> {
+    let locale = start
+        .try_get_attribute("locale")?
+        .map(|attr| attr.unescape_value().map(|v| v.into_owned()))
+        .transpose()?;
+
+    Ok(DescriptionField {
+        name: String::from_utf8_lossy(start.name().as_ref()).into_owned(),
+        locale,
+        value: String::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(content: &str) -> String {
+        patched_description(content, "42", &DescriptionEdits::default()).unwrap()
+    }
+
+    fn patch_error(content: &str) -> String {
+        let err = patched_description(content, "42", &DescriptionEdits::default()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        err.to_string()
+    }
+
+    #[test]
+    fn rewrite_fields_keeps_everything_else() {
+        let content = "<?xml version=\"1.0\"?>\n<theme>\n  <!-- 编号 -->\n  <id>1</id>\n  <author>me</author>\n</theme>\n";
+        let rewritten = rewrite_fields(content, |field| {
+            (field.name == "id").then(|| "2".to_string())
+        })
+        .unwrap();
+        assert_eq!(rewritten, content.replace("<id>1</id>", "<id>2</id>"));
+    }
+
+    #[test]
+    fn rewrite_fields_escapes_text_and_keeps_cdata() {
+        let content = r#"<theme><id>1</id><title locale="zh_CN"><![CDATA[旧]]></title></theme>"#;
+        let rewritten = rewrite_fields(content, |field| match field.name.as_str() {
+            "id" => Some("a<b".to_string()),
+            "title" => Some("新 & <标题>".to_string()),
+            _ => None,
+        })
+        .unwrap();
+        assert_eq!(
+            rewritten,
+            r#"<theme><id>a&lt;b</id><title locale="zh_CN"><![CDATA[新 & <标题>]]></title></theme>"#
+        );
+    }
+
+    #[test]
+    fn rewrite_fields_expands_empty_elements() {
+        let content = "<theme><id/><author /></theme>";
+        let rewritten = rewrite_fields(content, |field| Some(format!("{}!", field.name))).unwrap();
+        assert_eq!(
+            rewritten,
+            "<theme><id>id!</id><author>author!</author></theme>"
+        );
+    }
+
+    #[test]
+    fn attribute_order_does_not_matter() {
+        let content =
+            r#"<theme><id>1</id><title translatable="true" locale="zh_CN">旧</title></theme>"#;
+        assert_eq!(
+            patch(content),
+            r#"<theme><id>42</id><title translatable="true" locale="zh_CN">42</title></theme>"#
+        );
+    }
+
+    #[test]
+    fn single_quoted_attributes() {
+        let content = "<theme><id>1</id><title locale='zh_CN'>旧</title><title locale='en_US'>Old</title></theme>";
+        assert_eq!(
+            patch(content),
+            "<theme><id>42</id><title locale='zh_CN'>42</title><title locale='en_US'>Old</title></theme>"
+        );
+    }
+
+    #[test]
+    fn title_without_cdata_is_escaped() {
+        let content = r#"<theme><id>1</id><title locale="zh_CN">旧</title></theme>"#;
+        let edits = DescriptionEdits {
+            title_strategy: TitleStrategy::Keep,
+            titles: vec![LocalizedText {
+                locale: "zh_CN".to_string(),
+                value: "春 & 夏".to_string(),
+            }],
+            ..DescriptionEdits::default()
+        };
+        assert_eq!(
+            patched_description(content, "42", &edits).unwrap(),
+            r#"<theme><id>42</id><title locale="zh_CN">春 &amp; 夏</title></theme>"#
+        );
+    }
+
+    #[test]
+    fn title_inside_comment_is_ignored() {
+        let content = "<theme>\n  <!-- <title locale=\"zh_CN\">注释</title> -->\n  <id>1</id>\n  <title locale=\"zh_CN\">旧</title>\n</theme>";
+        let patched = patch(content);
+        assert!(patched.contains("<!-- <title locale=\"zh_CN\">注释</title> -->"));
+        assert!(patched.contains("<title locale=\"zh_CN\">42</title>"));
+
+        let only_comment = "<theme><!-- <title locale=\"zh_CN\">注释</title> --><id>1</id></theme>";
+        assert!(patch_error(only_comment).contains(r#"<title locale="zh_CN">"#));
+    }
+
+    #[test]
+    fn bom_is_preserved() {
+        let content = "\u{feff}<theme><id>1</id><title locale=\"zh_CN\">旧</title></theme>";
+        let patched = patch(content);
+        assert!(patched.starts_with('\u{feff}'));
+        assert_eq!(
+            patched.trim_start_matches(BOM),
+            "<theme><id>42</id><title locale=\"zh_CN\">42</title></theme>"
+        );
+        assert_eq!(
+            read_id_and_title(&patched),
+            (Some("42".to_string()), Some("42".to_string()))
+        );
+    }
+
+    #[test]
+    fn crlf_is_preserved() {
+        let content =
+            "<theme>\r\n\t<id>1</id>\r\n\t<title locale=\"zh_CN\">旧</title>\r\n</theme>\r\n";
+        let edits = DescriptionEdits {
+            author: Some("vlp".to_string()),
+            ..DescriptionEdits::default()
+        };
+        let patched = patched_description(content, "42", &edits).unwrap();
+        assert_eq!(
+            patched,
+            "<theme>\r\n\t<id>42</id>\r\n\t<title locale=\"zh_CN\">42</title>\r\n\t<author>vlp</author>\r\n</theme>\r\n"
+        );
+        assert!(!patched.replace("\r\n", "").contains('\n'));
+    }
+
+    #[test]
+    fn unchanged_fields_keep_their_layout() {
+        let content = "<theme>\n  <id>42</id>\n  <title locale=\"zh_CN\">春</title>\n  <description locale=\"zh_CN\">\n    第一行\n    第二行\n  </description>\n  <author> me </author>\n</theme>\n";
+        let edits = DescriptionEdits {
+            title_strategy: TitleStrategy::Keep,
+            ..DescriptionEdits::default()
+        };
+        assert_eq!(patched_description(content, "42", &edits).unwrap(), content);
+
+        let edits = DescriptionEdits {
+            author: Some("vlp".to_string()),
+            ..edits
+        };
+        assert_eq!(
+            patched_description(content, "42", &edits).unwrap(),
+            content.replace("<author> me </author>", "<author>vlp</author>")
+        );
+    }
+
+    #[test]
+    fn missing_id_and_title_are_reported() {
+        let message = patch_error("<theme><author>me</author></theme>");
+        assert!(message.contains("<id>"), "{message}");
+        assert!(message.contains(r#"<title locale="zh_CN">"#), "{message}");
+
+        let message = patch_error(r#"<theme><id> </id><title locale="zh_CN">t</title></theme>"#);
+        assert!(message.contains("<id>"), "{message}");
+        assert!(!message.contains("title"), "{message}");
+
+        let message = patch_error(r#"<theme><id>1</id><title locale="en_US">t</title></theme>"#);
+        assert!(message.contains(r#"<title locale="zh_CN">"#), "{message}");
+    }
+}
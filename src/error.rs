@@ -0,0 +1,293 @@
+use crate::check::Problem;
+use serde::Serialize;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::ExitStatus;
+
+/// 打包流程中的阶段, 用于在错误信息中指明出错的步骤
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// 校验锁屏包
+    Validate,
+    /// Step 1: 打包 lockscreen 目录
+    Lockscreen,
+    /// Step 2: 修复 description.xml
+    Description,
+    /// Step 3: 组装 .itz 文件
+    Itz,
+    /// Step 4: 生成最终输出文件
+    Output,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Stage::Validate => "校验锁屏包",
+            Stage::Lockscreen => "Step 1 打包 lockscreen 目录",
+            Stage::Description => "Step 2 修复 description.xml",
+            Stage::Itz => "Step 3 组装 .itz 文件",
+            Stage::Output => "Step 4 生成输出文件",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Debug)]
+pub enum ArchiveError {
+    /// 命令行参数、配置或输入路径无效
+    InvalidPath(io::Error),
+    /// 锁屏包缺少必要文件
+    MissingFiles {
+        package: PathBuf,
+        missing: Vec<String>,
+    },
+    /// 锁屏包未通过打包前的检查
+    CheckFailed {
+        package: PathBuf,
+        problems: Vec<Problem>,
+    },
+    /// 外部 zip 程序执行失败
+    ZipProcess {
+        stage: Option<Stage>,
+        /// 正在生成的 zip 文件
+        path: PathBuf,
+        program: PathBuf,
+        status: ExitStatus,
+        stdout: String,
+        stderr: String,
+    },
+    /// description.xml 格式错误或修改失败
+    Description {
+        stage: Option<Stage>,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// 不支持的文件类型（如设备文件、管道）
+    UnsupportedFileType { stage: Option<Stage>, path: PathBuf },
+    /// 写入最终输出文件失败
+    OutputWrite {
+        stage: Option<Stage>,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// 批量打包或检查中有锁屏包失败
+    BatchFailed { failed: usize, total: usize },
+    /// 其他 IO 错误
+    Io {
+        stage: Option<Stage>,
+        path: Option<PathBuf>,
+        source: io::Error,
+    },
+}
+
+impl ArchiveError {
+    /// 与 `path` 相关的 IO 错误
+    pub fn io_at<P: AsRef<Path>>(path: P, source: io::Error) -> Self {
+        ArchiveError::Io {
+            stage: None,
+            path: Some(path.as_ref().to_path_buf()),
+            source,
+        }
+    }
+
+    /// 记录出错的阶段, 已记录阶段的错误保持不变
+    pub fn in_stage(mut self, new_stage: Stage) -> Self {
+        match &mut self {
+            ArchiveError::ZipProcess { stage, .. }
+            | ArchiveError::Description { stage, .. }
+            | ArchiveError::UnsupportedFileType { stage, .. }
+            | ArchiveError::OutputWrite { stage, .. }
+            | ArchiveError::Io { stage, .. } => {
+                stage.get_or_insert(new_stage);
+            }
+            ArchiveError::InvalidPath(_)
+            | ArchiveError::MissingFiles { .. }
+            | ArchiveError::CheckFailed { .. }
+            | ArchiveError::BatchFailed { .. } => {}
+        }
+        self
+    }
+
+    /// 出错的阶段
+    pub fn stage(&self) -> Option<Stage> {
+        match self {
+            ArchiveError::ZipProcess { stage, .. }
+            | ArchiveError::Description { stage, .. }
+            | ArchiveError::UnsupportedFileType { stage, .. }
+            | ArchiveError::OutputWrite { stage, .. }
+            | ArchiveError::Io { stage, .. } => *stage,
+            ArchiveError::MissingFiles { .. } | ArchiveError::CheckFailed { .. } => {
+                Some(Stage::Validate)
+            }
+            ArchiveError::InvalidPath(_) | ArchiveError::BatchFailed { .. } => None,
+        }
+    }
+
+    /// 错误类型的名称, 用于 JSON 结果中的 `error.kind`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ArchiveError::InvalidPath(_) => "invalid_path",
+            ArchiveError::MissingFiles { .. } => "missing_files",
+            ArchiveError::ZipProcess { .. } => "zip_process",
+            ArchiveError::Description { .. } => "description",
+            ArchiveError::UnsupportedFileType { .. } => "unsupported_file_type",
+            ArchiveError::OutputWrite { .. } => "output_write",
+            ArchiveError::BatchFailed { .. } => "batch_failed",
+            ArchiveError::CheckFailed { .. } => "check_failed",
+            ArchiveError::Io { .. } => "io",
+        }
+    }
+
+    /// 进程退出码, 便于脚本区分错误类型
+    ///
+    /// | 退出码 | 错误 |
+    /// | --- | --- |
+    /// | 1 | 其他 IO 错误 |
+    /// | 2 | 参数、配置或路径无效 |
+    /// | 3 | 锁屏包缺少必要文件 |
+    /// | 4 | description.xml 错误 |
+    /// | 5 | 不支持的文件类型 |
+    /// | 6 | 外部 zip 程序执行失败 |
+    /// | 7 | 写入输出文件失败 |
+    /// | 8 | 批量打包或检查中有锁屏包失败 |
+    /// | 9 | 锁屏包未通过检查 |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ArchiveError::Io { .. } => 1,
+            ArchiveError::InvalidPath(_) => 2,
+            ArchiveError::MissingFiles { .. } => 3,
+            ArchiveError::Description { .. } => 4,
+            ArchiveError::UnsupportedFileType { .. } => 5,
+            ArchiveError::ZipProcess { .. } => 6,
+            ArchiveError::OutputWrite { .. } => 7,
+            ArchiveError::BatchFailed { .. } => 8,
+            ArchiveError::CheckFailed { .. } => 9,
+        }
+    }
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(stage) = self.stage() {
+            write!(f, "[{stage}] ")?;
+        }
+
+        match self {
+            ArchiveError::InvalidPath(e) => write!(f, "{e}"),
+            ArchiveError::MissingFiles { package, missing } => write!(
+                f,
+                "锁屏包 {} 缺少以下必要文件: {}",
+                package.display(),
+                missing.join(", ")
+            ),
+            ArchiveError::CheckFailed { package, problems } => {
+                write!(
+                    f,
+                    "锁屏包 {} 未通过检查, 共 {} 个问题:",
+                    package.display(),
+                    problems.len()
+                )?;
+                for problem in problems {
+                    write!(f, "\n  {problem}")?;
+                }
+                Ok(())
+            }
+            ArchiveError::ZipProcess {
+                path,
+                program,
+                status,
+                stdout,
+                stderr,
+                ..
+            } => {
+                write!(
+                    f,
+                    "zip 命令执行失败 ({status}): {} -> {}",
+                    program.display(),
+                    path.display()
+                )?;
+                for (name, output) in [("stdout", stdout), ("stderr", stderr)] {
+                    let output = output.trim_end();
+                    if !output.is_empty() {
+                        write!(f, "\n{name}:\n{output}")?;
+                    }
+                }
+                Ok(())
+            }
+            ArchiveError::Description { path, source, .. } => {
+                write!(f, "{} 处理失败: {source}", path.display())
+            }
+            ArchiveError::UnsupportedFileType { path, .. } => {
+                write!(f, "不支持的文件类型: {}", path.display())
+            }
+            ArchiveError::OutputWrite { path, source, .. } => {
+                write!(f, "写入输出文件 {} 失败: {source}", path.display())
+            }
+            ArchiveError::BatchFailed { failed, total } => {
+                write!(f, "{failed}/{total} 个锁屏包处理失败")
+            }
+            ArchiveError::Io { path, source, .. } => match path {
+                Some(path) => write!(f, "{}: {source}", path.display()),
+                None => write!(f, "{source}"),
+            },
+        }
+    }
+}
+
+impl std::error::Error for ArchiveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ArchiveError::InvalidPath(source)
+            | ArchiveError::Description { source, .. }
+            | ArchiveError::OutputWrite { source, .. }
+            | ArchiveError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ArchiveError {
+    fn from(source: io::Error) -> Self {
+        ArchiveError::Io {
+            stage: None,
+            path: None,
+            source,
+        }
+    }
+}
+
+impl From<fs_extra::error::Error> for ArchiveError {
+    fn from(err: fs_extra::error::Error) -> Self {
+        match err.kind {
+            fs_extra::error::ErrorKind::Io(io_err) => io_err.into(),
+            fs_extra::error::ErrorKind::StripPrefix(e) => {
+                ArchiveError::InvalidPath(io::Error::new(io::ErrorKind::InvalidInput, e))
+            }
+            fs_extra::error::ErrorKind::OsString(s) => ArchiveError::InvalidPath(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid OsString: {s:?}"),
+            )),
+            _ => ArchiveError::InvalidPath(io::Error::other(format!(
+                "fs_extra error: {:?}",
+                err.kind
+            ))),
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for ArchiveError {
+    fn from(err: zip::result::ZipError) -> Self {
+        io::Error::from(err).into()
+    }
+}
+
+impl From<walkdir::Error> for ArchiveError {
+    fn from(err: walkdir::Error) -> Self {
+        match err.path().map(Path::to_path_buf) {
+            Some(path) => ArchiveError::io_at(path, err.into()),
+            None => io::Error::from(err).into(),
+        }
+    }
+}
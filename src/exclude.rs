@@ -0,0 +1,166 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// 锁屏包根目录下的排除规则文件名, 语法与 .gitignore 相同
+pub const IGNORE_FILE_NAME: &str = ".vlpignore";
+
+/// 内置的排除规则: 系统生成的垃圾文件、版本控制目录、编辑器临时文件与设计源文件
+const DEFAULT_RULES: &[&str] = &[
+    ".DS_Store",
+    "._*",
+    "__MACOSX/",
+    "Thumbs.db",
+    "ehthumbs.db",
+    "desktop.ini",
+    ".git/",
+    ".svn/",
+    ".hg/",
+    "*.swp",
+    "*.swo",
+    "*~",
+    "*.tmp",
+    "*.psd",
+    "*.ai",
+    "*.sketch",
+];
+
+/// 打包时的文件排除规则, 同时记录被排除的文件
+///
+/// 规则相对于锁屏包根目录; 不在根目录下的路径（如临时目录中的中间文件）从不排除。
+/// 克隆后的实例共享同一份排除记录。
+#[derive(Clone)]
+pub struct ExcludeRules {
+    matcher: Gitignore,
+    /// 调用方传入的锁屏包根目录; `matcher.path()` 会去掉开头的 `./`, 与遍历得到的路径不一致
+    root: PathBuf,
+    excluded: Arc<Mutex<Vec<PathBuf>>>,
+}
+
+impl ExcludeRules {
+    /// 不排除任何文件
+    pub fn none() -> Self {
+        ExcludeRules {
+            matcher: Gitignore::empty(),
+            root: PathBuf::new(),
+            excluded: Arc::default(),
+        }
+    }
+
+    /// 内置规则 + `extra`（如 vlp.toml 中的 exclude）+ 锁屏包根目录下的 .vlpignore, 后者优先级最高
+    pub fn load(package_dir: &Path, extra: &[String]) -> io::Result<Self> {
+        let invalid = |e: ignore::Error| io::Error::new(io::ErrorKind::InvalidData, e.to_string());
+
+        let mut builder = GitignoreBuilder::new(package_dir);
+        for rule in DEFAULT_RULES
+            .iter()
+            .copied()
+            .chain(extra.iter().map(String::as_str))
+        {
+            builder.add_line(None, rule).map_err(invalid)?;
+        }
+
+        let ignore_file = package_dir.join(IGNORE_FILE_NAME);
+        if ignore_file.is_file()
+            && let Some(e) = builder.add(&ignore_file)
+        {
+            return Err(invalid(e));
+        }
+
+        Ok(ExcludeRules {
+            matcher: builder.build().map_err(invalid)?,
+            root: package_dir.to_path_buf(),
+            excluded: Arc::default(),
+        })
+    }
+
+    /// 判断 `path` 是否应被排除, 被排除的路径会被记录下来
+    ///
+    /// 调用方遍历目录时应跳过被排除的目录, 因此这里只检查路径本身。
+    pub fn is_excluded(&self, path: &Path, is_dir: bool) -> bool {
+        if self.matcher.is_empty() {
+            return false;
+        }
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        if relative.as_os_str().is_empty() {
+            return false;
+        }
+
+        let excluded = self.matcher.matched(relative, is_dir).is_ignore();
+        if excluded {
+            self.excluded.lock().unwrap().push(relative.to_path_buf());
+        }
+        excluded
+    }
+
+    /// 目前为止被排除的路径（相对于锁屏包根目录）
+    pub fn excluded(&self) -> Vec<PathBuf> {
+        let mut excluded = self.excluded.lock().unwrap().clone();
+        excluded.sort();
+        excluded.dedup();
+        excluded
+    }
+}
+
+impl Default for ExcludeRules {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn dot_slash_root_matches_walked_paths() {
+        // 与 `vlp ./pkg` 相同, 遍历得到的路径保留开头的 `./`
+        let root = Path::new("./pkg");
+        let rules = ExcludeRules::load(root, &[]).unwrap();
+        assert!(rules.is_excluded(&root.join("lockscreen/.DS_Store"), false));
+        assert!(rules.is_excluded(&root.join("lockscreen/images/a.psd"), false));
+        assert!(!rules.is_excluded(&root.join("lockscreen/images/a.png"), false));
+        assert!(!rules.is_excluded(root, true));
+        assert!(!rules.is_excluded(Path::new("/tmp/lockscreen.zip"), false));
+        assert_eq!(
+            rules.excluded(),
+            [
+                PathBuf::from("lockscreen/.DS_Store"),
+                PathBuf::from("lockscreen/images/a.psd"),
+            ]
+        );
+    }
+
+    #[test]
+    fn directory_rules_match_nested_directories_only() {
+        let root = Path::new("pkg");
+        let extra = ["build/".to_string(), "/lockscreen/drafts/".to_string()];
+        let rules = ExcludeRules::load(root, &extra).unwrap();
+        assert!(rules.is_excluded(&root.join("lockscreen/images/build"), true));
+        assert!(!rules.is_excluded(&root.join("lockscreen/images/build"), false));
+        assert!(rules.is_excluded(&root.join("lockscreen/drafts"), true));
+        assert!(!rules.is_excluded(&root.join("lockscreen/images/drafts"), true));
+        assert!(rules.is_excluded(&root.join("lockscreen/.git"), true));
+    }
+
+    #[test]
+    fn vlpignore_overrides_builtin_rules() {
+        let tmp = TempDir::new().unwrap();
+        let root = tmp.path();
+        fs::write(root.join(IGNORE_FILE_NAME), "!keep.psd\n!readme.txt\n").unwrap();
+
+        let rules = ExcludeRules::load(root, &["*.txt".to_string()]).unwrap();
+        assert!(!rules.is_excluded(&root.join("lockscreen/keep.psd"), false));
+        assert!(rules.is_excluded(&root.join("lockscreen/other.psd"), false));
+        assert!(rules.is_excluded(&root.join("notes.txt"), false));
+        assert!(!rules.is_excluded(&root.join("readme.txt"), false));
+
+        let rules = ExcludeRules::none();
+        assert!(!rules.is_excluded(&root.join("lockscreen/other.psd"), false));
+    }
+}
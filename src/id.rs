@@ -0,0 +1,565 @@
+use crate::inspect::read_lockscreen_id;
+use crate::utils::{user_data_dir, validate_id};
+use chrono::{DateTime, Local};
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// 默认的编号模板: 日期 + 三位序号, 如 20250101001
+pub const DEFAULT_TEMPLATE: &str = "{date}{seq:03}";
+
+/// 随机分配时遇到已使用编号的最大重试次数
+const RANDOM_ATTEMPTS: usize = 1000;
+
+/// 指定编号登记簿路径的环境变量
+pub const REGISTRY_ENV: &str = "VLP_ID_REGISTRY";
+
+/// 新编号的分配方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum IdStrategy {
+    /// 随机序号, 避开已使用的编号
+    #[default]
+    Random,
+    /// 每天从 1 开始递增的序号
+    Sequence,
+}
+
+/// 未指定编号时锁屏编号的来源
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IdMode {
+    /// 沿用 vlp.lock 中的编号, 没有时分配新编号
+    #[default]
+    Locked,
+    /// 沿用源 description.xml 中的编号
+    KeepDescription,
+    /// 总是分配新编号
+    New,
+}
+
+/// 锁屏编号模板, 支持 `{date}`（yyyymmdd）与 `{seq}`/`{seq:03}`（序号, 可指定补零宽度）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdTemplate {
+    parts: Vec<TemplatePart>,
+    /// 序号的补零宽度, `{seq}` 为 None
+    width: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TemplatePart {
+    Literal(String),
+    Date,
+    Seq,
+}
+
+impl IdTemplate {
+    /// 序号的取值范围: 指定宽度时不超过该宽度, 否则为 1-9999
+    fn seq_range(&self) -> (u32, u32) {
+        match self.width {
+            Some(width) => (1, 10u32.saturating_pow(width as u32).saturating_sub(1)),
+            None => (1, 9999),
+        }
+    }
+
+    /// 按模板在 `date` 当天生成的编号 `id` 所用的序号, 不是由该模板生成的编号返回 None
+    fn parse_seq(&self, date: &str, id: &str) -> Option<u32> {
+        // 第一个 {seq} 之前只有固定文本与日期
+        let mut prefix = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => prefix.push_str(text),
+                TemplatePart::Date => prefix.push_str(date),
+                TemplatePart::Seq => break,
+            }
+        }
+        let rest = id.strip_prefix(&prefix)?;
+        let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+        // 序号之后的固定文本也可能以数字开头, 从长到短逐一尝试, 以重新生成的编号相同为准
+        (1..=digits.min(10))
+            .rev()
+            .filter_map(|len| rest[..len].parse().ok())
+            .find(|&seq| self.render(date, seq) == id)
+    }
+
+    pub fn render(&self, date: &str, seq: u32) -> String {
+        let mut id = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => id.push_str(text),
+                TemplatePart::Date => id.push_str(date),
+                TemplatePart::Seq => match self.width {
+                    Some(width) => id.push_str(&format!("{seq:0width$}")),
+                    None => id.push_str(&seq.to_string()),
+                },
+            }
+        }
+        id
+    }
+}
+
+impl FromStr for IdTemplate {
+    type Err = io::Error;
+
+    fn from_str(template: &str) -> Result<Self, Self::Err> {
+        let invalid = |message: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("无效的编号模板 {template:?}: {message}"),
+            )
+        };
+
+        let mut parts = Vec::new();
+        let mut width = None;
+        let mut has_seq = false;
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(TemplatePart::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}').ok_or_else(|| invalid("缺少 '}'"))?;
+            let placeholder = &rest[start + 1..start + end];
+            match placeholder.split_once(':') {
+                None if placeholder == "date" => parts.push(TemplatePart::Date),
+                None if placeholder == "seq" => {
+                    has_seq = true;
+                    parts.push(TemplatePart::Seq);
+                }
+                Some(("seq", spec)) => {
+                    let digits = spec.parse::<usize>().ok().filter(|n| (1..=9).contains(n));
+                    width = Some(digits.ok_or_else(|| invalid("序号宽度应为 1-9"))?);
+                    has_seq = true;
+                    parts.push(TemplatePart::Seq);
+                }
+                _ => return Err(invalid(&format!("未知的占位符 {{{placeholder}}}"))),
+            }
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(TemplatePart::Literal(rest.to_string()));
+        }
+        if !has_seq {
+            return Err(invalid("缺少 {seq}"));
+        }
+        // 生成的编号会用作文件名, 固定文本同样只能使用 validate_id 允许的字符
+        for part in &parts {
+            if let TemplatePart::Literal(text) = part {
+                validate_id(text).map_err(|_| invalid("固定文本只能包含字母、数字、_ 与 -"))?;
+            }
+        }
+
+        Ok(IdTemplate { parts, width })
+    }
+}
+
+impl Default for IdTemplate {
+    fn default() -> Self {
+        DEFAULT_TEMPLATE.parse().unwrap()
+    }
+}
+
+impl<'de> Deserialize<'de> for IdTemplate {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let template = String::deserialize(deserializer)?;
+        template.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for IdTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => f.write_str(text)?,
+                TemplatePart::Date => f.write_str("{date}")?,
+                TemplatePart::Seq => match self.width {
+                    Some(width) => write!(f, "{{seq:0{width}}}")?,
+                    None => f.write_str("{seq}")?,
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 新编号的分配规则: 分配方式、编号模板与编号登记簿
+///
+/// 登记簿每行记录一个已分配的编号, 读写时加文件锁, 可放在团队共享目录中供多人、多进程共用。
+#[derive(Debug, Clone, Default)]
+pub struct IdPolicy {
+    strategy: IdStrategy,
+    template: IdTemplate,
+    registry: Option<PathBuf>,
+}
+
+impl IdPolicy {
+    /// `registry` 为 None 时只避开输出目录中已有锁屏文件的编号
+    pub fn new(strategy: IdStrategy, template: IdTemplate, registry: Option<PathBuf>) -> Self {
+        IdPolicy {
+            strategy,
+            template,
+            registry,
+        }
+    }
+
+    /// 分配一个新编号并记入登记簿, 避开登记簿中与 `output_dir` 下已有锁屏文件使用的编号
+    pub fn allocate(&self, input: &Path, output_dir: &Path) -> io::Result<String> {
+        let now = Local::now();
+        let mut registry = self.open_registry()?;
+        let mut used = existing_output_ids(output_dir);
+        if let Some(registry) = &mut registry {
+            used.extend(registry.ids()?);
+        }
+
+        let id = self.pick(&now.format("%Y%m%d").to_string(), &used)?;
+        if let Some(registry) = &mut registry {
+            registry.append(&id, input, now)?;
+        }
+        Ok(id)
+    }
+
+    /// 预览 [`allocate`](Self::allocate) 会分配的编号, 不加锁也不写入登记簿
+    ///
+    /// 随机分配时每次结果不同, 实际打包时分配的编号以打包结果为准。
+    pub fn preview(&self, output_dir: &Path) -> io::Result<String> {
+        let mut used = existing_output_ids(output_dir);
+        if let Some(path) = &self.registry {
+            match fs::read_to_string(path) {
+                Ok(content) => used.extend(parse_registry(&content)),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => {
+                    return Err(io::Error::new(e.kind(), format!("{}: {e}", path.display())));
+                }
+            }
+        }
+        self.pick(&Local::now().format("%Y%m%d").to_string(), &used)
+    }
+
+    /// 按分配方式选出 `date` 当天未被使用的编号
+    ///
+    /// 顺序分配取已使用编号中当天最大的序号加 1; 随机分配随机抽取序号, 重复时重试,
+    /// 最多 [`RANDOM_ATTEMPTS`] 次。两者的开销都与序号宽度无关。
+    fn pick(&self, date: &str, used: &HashSet<String>) -> io::Result<String> {
+        let (min, max) = self.template.seq_range();
+        let id = match self.strategy {
+            IdStrategy::Sequence => {
+                let last = used
+                    .iter()
+                    .filter_map(|id| self.template.parse_seq(date, id))
+                    .max();
+                last.map_or(Some(min), |seq| {
+                    seq.checked_add(1).filter(|&seq| seq <= max)
+                })
+                .map(|seq| self.template.render(date, seq))
+            }
+            IdStrategy::Random => {
+                // 与旧版一致, 指定宽度时随机序号不含前导零
+                let min = match self.template.width {
+                    Some(width) if width > 1 => 10u32.pow(width as u32 - 1),
+                    _ => min,
+                };
+                let mut rng = rand::rng();
+                (0..RANDOM_ATTEMPTS)
+                    .map(|_| self.template.render(date, rng.random_range(min..=max)))
+                    .find(|id| !used.contains(id))
+            }
+        };
+        id.ok_or_else(|| {
+            io::Error::other(format!(
+                "编号模板 {} 在 {date} 已没有可用的编号",
+                self.template
+            ))
+        })
+    }
+
+    /// 将指定或沿用的编号记入登记簿（已登记时不重复记录）, 避免之后被重新分配
+    pub fn register(&self, id: &str, input: &Path) -> io::Result<()> {
+        if let Some(mut registry) = self.open_registry()?
+            && !registry.ids()?.contains(id)
+        {
+            registry.append(id, input, Local::now())?;
+        }
+        Ok(())
+    }
+
+    /// 撤销 [`allocate`](Self::allocate) 为 `input` 预留的编号, 用于打包失败时
+    pub fn release(&self, id: &str, input: &Path) -> io::Result<()> {
+        if let Some(mut registry) = self.open_registry()? {
+            registry.remove(id, input)?;
+        }
+        Ok(())
+    }
+
+    fn open_registry(&self) -> io::Result<Option<Registry>> {
+        self.registry.as_deref().map(Registry::open).transpose()
+    }
+}
+
+/// 默认的登记簿位置: 环境变量 `VLP_ID_REGISTRY`, 否则为用户数据目录下的 `vlp/ids.txt`
+pub fn default_registry_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(REGISTRY_ENV).filter(|path| !path.is_empty()) {
+        return Some(PathBuf::from(path));
+    }
+
+    user_data_dir().map(|dir| dir.join("vlp").join("ids.txt"))
+}
+
+/// 已加锁的登记簿, 锁在 drop 时释放
+struct Registry {
+    path: PathBuf,
+    file: File,
+}
+
+impl Registry {
+    fn open(path: &Path) -> io::Result<Self> {
+        let with_path = |e: io::Error| io::Error::new(e.kind(), format!("{}: {e}", path.display()));
+        if let Some(parent) = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            fs::create_dir_all(parent).map_err(with_path)?;
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(with_path)?;
+        file.lock().map_err(with_path)?;
+
+        Ok(Registry {
+            path: path.to_path_buf(),
+            file,
+        })
+    }
+
+    fn ids(&mut self) -> io::Result<HashSet<String>> {
+        let mut content = String::new();
+        self.file
+            .read_to_string(&mut content)
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", self.path.display())))?;
+        Ok(parse_registry(&content))
+    }
+
+    fn append(&mut self, id: &str, input: &Path, time: DateTime<Local>) -> io::Result<()> {
+        let line = format!("{id}\t{}\t{}\n", time.to_rfc3339(), input.display());
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", self.path.display())))
+    }
+
+    /// 删除最后一条为 `input` 登记 `id` 的记录
+    fn remove(&mut self, id: &str, input: &Path) -> io::Result<()> {
+        let with_path =
+            |e: io::Error| io::Error::new(e.kind(), format!("{}: {e}", self.path.display()));
+        let mut content = String::new();
+        self.file.read_to_string(&mut content).map_err(with_path)?;
+
+        let input = input.display().to_string();
+        let mut lines: Vec<&str> = content.lines().collect();
+        let Some(index) = lines.iter().rposition(|line| {
+            let mut fields = line.split('\t');
+            fields.next() == Some(id) && fields.nth(1) == Some(input.as_str())
+        }) else {
+            return Ok(());
+        };
+        lines.remove(index);
+
+        let mut content = lines.join("\n");
+        if !content.is_empty() {
+            content.push('\n');
+        }
+        // 以追加方式打开, 清空后写入的内容从文件开头开始
+        self.file
+            .set_len(0)
+            .and_then(|_| self.file.write_all(content.as_bytes()))
+            .and_then(|_| self.file.sync_data())
+            .map_err(with_path)
+    }
+}
+
+/// 登记簿中的编号: 每行第一列为编号, `#` 开头的行为注释
+fn parse_registry(content: &str) -> HashSet<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+/// `output_dir` 下已有锁屏文件的编号, 只读取看起来是锁屏文件的 zip, 无法读取的文件忽略
+fn existing_output_ids(output_dir: &Path) -> HashSet<String> {
+    let Ok(entries) = fs::read_dir(output_dir) else {
+        return HashSet::new();
+    };
+    entries
+        .flatten()
+        .filter(|entry| entry.file_type().is_ok_and(|file_type| file_type.is_file()))
+        .filter_map(|entry| read_lockscreen_id(&entry.path()).ok().flatten())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(strategy: IdStrategy, template: &str) -> IdPolicy {
+        IdPolicy::new(strategy, template.parse().unwrap(), None)
+    }
+
+    fn used(ids: &[&str]) -> HashSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    fn parse_error(template: &str) -> String {
+        let err = template.parse::<IdTemplate>().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{template}");
+        err.to_string()
+    }
+
+    #[test]
+    fn template_round_trips() {
+        for template in [
+            "{date}{seq:03}",
+            "{seq}",
+            "v{seq:01}-{date}",
+            "lock_{seq:09}",
+        ] {
+            let parsed: IdTemplate = template.parse().unwrap();
+            assert_eq!(parsed.to_string(), template);
+        }
+        let parsed: IdTemplate = "a{date}{seq:03}".parse().unwrap();
+        assert_eq!(parsed.render("20250101", 7), "a20250101007");
+    }
+
+    #[test]
+    fn template_rejects_unknown_placeholder() {
+        assert!(parse_error("{date}{seq}{title}").contains("未知的占位符 {title}"));
+        assert!(parse_error("{date:03}{seq}").contains("未知的占位符"));
+        assert!(parse_error("{}{seq}").contains("未知的占位符"));
+    }
+
+    #[test]
+    fn template_rejects_missing_brace() {
+        assert!(parse_error("{date}{seq").contains("缺少 '}'"));
+        assert!(parse_error("{seq}{date").contains("缺少 '}'"));
+    }
+
+    #[test]
+    fn template_width_bounds() {
+        assert_eq!("{seq:1}".parse::<IdTemplate>().unwrap().seq_range(), (1, 9));
+        assert_eq!(
+            "{seq:9}".parse::<IdTemplate>().unwrap().seq_range(),
+            (1, 999_999_999)
+        );
+        assert_eq!(
+            "{seq}".parse::<IdTemplate>().unwrap().seq_range(),
+            (1, 9999)
+        );
+        for template in ["{seq:0}", "{seq:10}", "{seq:}", "{seq:x}", "{seq:-1}"] {
+            assert!(
+                parse_error(template).contains("序号宽度应为 1-9"),
+                "{template}"
+            );
+        }
+    }
+
+    #[test]
+    fn template_requires_seq() {
+        assert!(parse_error("{date}").contains("缺少 {seq}"));
+        assert!(parse_error("fixed").contains("缺少 {seq}"));
+        assert!(parse_error("").contains("缺少 {seq}"));
+    }
+
+    #[test]
+    fn template_rejects_unsafe_literals() {
+        for template in ["../{seq}", "{date}/{seq}", "{seq}.zip", "a b{seq}"] {
+            assert!(parse_error(template).contains("固定文本"), "{template}");
+        }
+    }
+
+    #[test]
+    fn sequence_starts_at_one() {
+        let policy = policy(IdStrategy::Sequence, "{date}{seq:03}");
+        assert_eq!(policy.pick("20250101", &used(&[])).unwrap(), "20250101001");
+    }
+
+    #[test]
+    fn sequence_continues_after_highest_used_seq_of_the_day() {
+        let policy = policy(IdStrategy::Sequence, "{date}{seq:03}");
+        let used = used(&["20250101001", "20250101007", "20250102042", "other"]);
+        assert_eq!(policy.pick("20250101", &used).unwrap(), "20250101008");
+    }
+
+    #[test]
+    fn sequence_ignores_ids_from_other_templates() {
+        let policy = policy(IdStrategy::Sequence, "v{seq}-{date}");
+        let used = used(&[
+            "v12-20250101",
+            "v012-20250101",
+            "v99-20250102",
+            "x5-20250101",
+        ]);
+        assert_eq!(policy.pick("20250101", &used).unwrap(), "v13-20250101");
+    }
+
+    #[test]
+    fn sequence_with_digits_after_seq() {
+        let policy = policy(IdStrategy::Sequence, "{seq}1");
+        assert_eq!(
+            policy.pick("20250101", &used(&["51", "1231"])).unwrap(),
+            "1241"
+        );
+    }
+
+    #[test]
+    fn sequence_fails_when_exhausted() {
+        let policy = policy(IdStrategy::Sequence, "{date}{seq:01}");
+        assert!(policy.pick("20250101", &used(&["202501019"])).is_err());
+    }
+
+    #[test]
+    fn random_avoids_used_ids() {
+        let policy = policy(IdStrategy::Random, "{date}{seq:01}");
+        let used: HashSet<String> = (1..=8).map(|seq| format!("20250101{seq}")).collect();
+        for _ in 0..20 {
+            assert_eq!(policy.pick("20250101", &used).unwrap(), "202501019");
+        }
+    }
+
+    #[test]
+    fn random_fails_when_exhausted() {
+        let policy = policy(IdStrategy::Random, "{date}{seq:01}");
+        let used: HashSet<String> = (1..=9).map(|seq| format!("20250101{seq}")).collect();
+        assert!(policy.pick("20250101", &used).is_err());
+    }
+
+    #[test]
+    fn random_has_no_leading_zeros() {
+        let policy = policy(IdStrategy::Random, "{seq:03}");
+        for _ in 0..100 {
+            let id = policy.pick("20250101", &used(&[])).unwrap();
+            assert!(!id.starts_with('0'), "{id}");
+        }
+    }
+
+    #[test]
+    fn large_width_is_fast() {
+        let random = policy(IdStrategy::Random, "{date}{seq:09}");
+        let id = random.pick("20250101", &used(&[])).unwrap();
+        assert_eq!(id.len(), 17);
+
+        let sequence = policy(IdStrategy::Sequence, "{date}{seq:09}");
+        let used = used(&["20250101000000041"]);
+        assert_eq!(
+            sequence.pick("20250101", &used).unwrap(),
+            "20250101000000042"
+        );
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{
+    description::{DescriptionField, read_fields, read_id_and_title},
+    error::ArchiveError,
+};
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, Cursor, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// zip 文件开头的本地文件头签名
+const ZIP_MAGIC: [u8; 4] = *b"PK\x03\x04";
+
+/// 嵌套压缩包的最大展开层数（lockscreen → lockscreen.itz → lockscreen/<id>.zip）
+const MAX_NESTING: usize = 3;
+
+/// 读入内存的单个条目的大小上限, 超过时视为无效的压缩包
+pub(crate) const MAX_ENTRY_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// 打包好的 lockscreen 文件概要信息
+#[derive(Debug, Serialize)]
+pub struct PackageInfo {
+    pub path: String,
+    pub id: Option<String>,
+    pub description: Vec<DescriptionField>,
+    pub entries: Vec<EntryInfo>,
+    pub previews: Vec<PreviewInfo>,
+}
+
+/// 压缩包中的一个条目, 嵌套压缩包内的条目以 `外层条目/内层条目` 表示
+#[derive(Debug, Serialize)]
+pub struct EntryInfo {
+    pub path: String,
+    /// 条目在其所在压缩包中的名称
+    pub name: String,
+    /// 所在的嵌套层级, 0 表示最外层
+    pub depth: usize,
+    pub is_dir: bool,
+    pub compressed_size: u64,
+    pub size: u64,
+}
+
+/// 预览图及其尺寸
+#[derive(Debug, Serialize)]
+pub struct PreviewInfo {
+    pub path: String,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// 只读地读取 lockscreen 文件的描述信息、条目列表与预览图尺寸, 不解压到磁盘
+pub fn inspect_lockscreen_package(input: &Path) -> Result<PackageInfo, ArchiveError> {
+    let mut info = PackageInfo {
+        path: input.display().to_string(),
+        id: None,
+        description: Vec::new(),
+        entries: Vec::new(),
+        previews: Vec::new(),
+    };
+
+    let mut outer = open_zip(input)?;
+    collect_entries(&mut outer, "", 0, &mut info)?;
+
+    info.id = info
+        .description
+        .iter()
+        .find(|field| field.name == "id")
+        .map(|field| field.value.clone());
+
+    Ok(info)
+}
+
+/// 只读取 lockscreen 文件中 description.xml 的编号, 不是锁屏文件（不是 zip 或没有 lockscreen.itz）时返回 None
+///
+/// 比 [`inspect_lockscreen_package`] 开销小, 用于扫描输出目录中已有的编号。
+pub fn read_lockscreen_id(input: &Path) -> Result<Option<String>, ArchiveError> {
+    let mut file = File::open(input).map_err(|e| ArchiveError::io_at(input, e))?;
+    let mut magic = [0u8; 4];
+    if file.read_exact(&mut magic).is_err() || magic != ZIP_MAGIC {
+        return Ok(None);
+    }
+    file.rewind().map_err(|e| ArchiveError::io_at(input, e))?;
+
+    let mut outer = ZipArchive::new(file).map_err(|e| ArchiveError::io_at(input, e.into()))?;
+    let Ok(itz) = outer.by_name("lockscreen.itz") else {
+        return Ok(None);
+    };
+    let mut itz = ZipArchive::new(Cursor::new(read_capped(itz, "lockscreen.itz")?))?;
+    let Ok(description) = itz.by_name("description.xml") else {
+        return Ok(None);
+    };
+    let content = read_capped(description, "description.xml")?;
+    Ok(read_id_and_title(&String::from_utf8_lossy(&content)).0)
+}
+
+/// 打开 `input` 处的 zip 文件, 出错时在错误信息中带上文件路径
+pub(crate) fn open_zip(input: &Path) -> Result<ZipArchive<File>, ArchiveError> {
+    let file = File::open(input).map_err(|e| ArchiveError::io_at(input, e))?;
+    ZipArchive::new(file).map_err(|e| ArchiveError::io_at(input, e.into()))
+}
+
+fn collect_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    prefix: &str,
+    depth: usize,
+    info: &mut PackageInfo,
+) -> Result<(), ArchiveError> {
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let name = entry.name().to_string();
+
+        info.entries.push(EntryInfo {
+            path: format!("{prefix}{name}"),
+            name: name.clone(),
+            depth,
+            is_dir: entry.is_dir(),
+            compressed_size: entry.compressed_size(),
+            size: entry.size(),
+        });
+
+        if entry.is_dir() {
+            continue;
+        }
+
+        let is_archive = name.ends_with(".itz") || name.ends_with(".zip");
+        let is_description = name == "description.xml";
+        let is_preview = name.starts_with("preview/");
+        if !(is_archive && depth + 1 < MAX_NESTING || is_description || is_preview) {
+            continue;
+        }
+
+        let buffer = read_capped(&mut entry, &name)?;
+
+        if is_archive {
+            let mut nested = ZipArchive::new(Cursor::new(buffer))?;
+            collect_entries(&mut nested, &format!("{prefix}{name}/"), depth + 1, info)?;
+        } else if is_description {
+            let content = String::from_utf8_lossy(&buffer);
+            info.description = read_fields(&content).map_err(|e| {
+                ArchiveError::InvalidPath(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("description.xml 解析失败: {e}"),
+                ))
+            })?;
+        } else if let Ok(size) = imagesize::blob_size(&buffer) {
+            info.previews.push(PreviewInfo {
+                path: format!("{prefix}{name}"),
+                width: size.width,
+                height: size.height,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// 将压缩包条目读入内存, 不超过 [`MAX_ENTRY_SIZE`]
+///
+/// 不按 zip 头部声明的大小预分配: 该值来自文件本身, 可能被伪造得很大。
+pub(crate) fn read_capped(entry: impl Read, name: &str) -> io::Result<Vec<u8>> {
+    let mut buffer = Vec::new();
+    entry.take(MAX_ENTRY_SIZE + 1).read_to_end(&mut buffer)?;
+    if buffer.len() as u64 > MAX_ENTRY_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "条目 {name} 超过 {} MiB 的大小上限",
+                MAX_ENTRY_SIZE / 1024 / 1024
+            ),
+        ));
+    }
+    Ok(buffer)
+}
+
+impl std::fmt::Display for PackageInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "文件: {}", self.path)?;
+        writeln!(f, "编号: {}", self.id.as_deref().unwrap_or("-"))?;
+
+        writeln!(f, "\ndescription.xml:")?;
+        for field in &self.description {
+            match &field.locale {
+                Some(locale) => writeln!(f, "  {}[{}]: {}", field.name, locale, field.value)?,
+                None => writeln!(f, "  {}: {}", field.name, field.value)?,
+            }
+        }
+
+        writeln!(f, "\n{:>12} {:>12}  条目", "压缩后", "原始大小")?;
+        for entry in &self.entries {
+            let name = &entry.name;
+            let indent = "  ".repeat(entry.depth);
+            if entry.is_dir {
+                writeln!(f, "{:>12} {:>12}  {indent}{name}", "", "")?;
+            } else {
+                writeln!(
+                    f,
+                    "{:>12} {:>12}  {indent}{name}",
+                    entry.compressed_size, entry.size
+                )?;
+            }
+        }
+
+        if !self.previews.is_empty() {
+            writeln!(f, "\n预览图:")?;
+            for preview in &self.previews {
+                writeln!(
+                    f,
+                    "  {} ({}x{})",
+                    preview.path, preview.width, preview.height
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
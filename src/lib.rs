@@ -0,0 +1,39 @@
+//! Vivo 锁屏包打包库
+//!
+//! 命令行工具 `vlp` 的全部打包能力: 使用 [`PackageBuilder`] 将锁屏包目录打包为 lockscreen 文件,
+//! 打包结果以 [`PackOutput`] 返回, 不会输出任何内容（除非指定了 [`Reporter`]）。
+//! 解包与查看信息见 [`unpack`] 与 [`inspect`] 模块。
+
+pub mod archivefiles;
+pub mod archiver;
+pub mod builder;
+pub mod check;
+pub mod compression;
+pub mod config;
+pub mod description;
+pub mod error;
+pub mod exclude;
+pub mod id;
+pub mod inspect;
+pub mod lockfile;
+pub mod output;
+pub mod pipeline;
+pub mod plan;
+pub mod record;
+pub mod report;
+pub mod unpack;
+pub mod utils;
+pub mod zip_command;
+
+pub use archiver::{Archiver, ArchiverKind, NativeZip, resolve_archiver};
+pub use builder::PackageBuilder;
+pub use check::{CheckReport, Problem};
+pub use compression::CompressionPolicy;
+pub use description::{DescriptionEdits, LocalizedText, TitleStrategy};
+pub use error::{ArchiveError, Stage};
+pub use id::{IdMode, IdPolicy, IdStrategy, IdTemplate};
+pub use output::OnConflict;
+pub use pipeline::{PackOutput, PackSizes};
+pub use plan::PackPlan;
+pub use record::BuildRecord;
+pub use report::{PlainReporter, ProgressReporter, Reporter, SilentReporter};
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// 锁屏包根目录下记录锁屏编号的文件名
+pub const LOCKFILE_NAME: &str = "vlp.lock";
+
+/// 写入 `vlp.lock` 开头的说明
+const HEADER: &str = "# 由 vlp 生成, 重新打包时将复用此编号\n";
+
+/// 锁屏包的编号记录, 重新打包时复用其中的编号
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageLock {
+    pub id: String,
+}
+
+impl PackageLock {
+    /// 读取 `package_dir` 下的 `vlp.lock`, 文件不存在时返回 None
+    pub fn read<P: AsRef<Path>>(package_dir: P) -> io::Result<Option<Self>> {
+        let path = package_dir.as_ref().join(LOCKFILE_NAME);
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        toml::from_str(&content)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("格式错误: {e}")))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, package_dir: P) -> io::Result<()> {
+        let content = toml::to_string(self).map_err(io::Error::other)?;
+        fs::write(
+            package_dir.as_ref().join(LOCKFILE_NAME),
+            format!("{HEADER}{content}"),
+        )
+    }
+}
@@ -1,50 +1,24 @@
-mod archivefiles;
+mod batch;
 mod cli;
-mod pipeline;
+mod commands;
 mod register;
-mod utils;
-mod zip_command;
+mod watch;
 
-use crate::archivefiles::*;
-use crate::cli::Args;
-use crate::pipeline::process_lockscreen_package;
-use crate::register::{do_register, do_unregister};
-use crate::utils::pause_before_exit;
-use crate::zip_command::ZipCommand;
+use crate::cli::Cli;
+use crate::commands::Context;
 use clap::Parser;
 use console::Emoji;
+use vivo_lockscreen_packing::utils::pause_before_exit;
 
 static ERROR_EMOJI: Emoji<'_, '_> = Emoji("❌ ", "ERR");
-static SPARKLE: Emoji<'_, '_> = Emoji("✨ ", ":-)");
 
 fn main() {
-    if let Err(err) = run() {
+    let (command, global) = Cli::parse().into_command();
+    let pause_mode = global.pause_mode();
+    let context = Context::new(global, command.json());
+    if let Err(err) = context.run(&command) {
         eprintln!("{ERROR_EMOJI}程序运行出错, {err}");
-        pause_before_exit();
-        std::process::exit(1);
+        pause_before_exit(pause_mode);
+        std::process::exit(err.exit_code());
     }
 }
-
-fn run() -> Result<(), ArchiveError> {
-    let args = Args::parse();
-
-    if args.register {
-        do_register()?;
-        println!("{SPARKLE}右键功能注册成功!");
-        return Ok(());
-    } else if args.unregister {
-        do_unregister()?;
-        println!("{SPARKLE}右键功能取消注册成功!");
-        return Ok(());
-    }
-
-    args.validate_input()?;
-
-    process_lockscreen_package(
-        args.get_input_path()?,
-        &args.resolved_output_dir()?,
-        ZipCommand::resolve(args.zip_path.as_deref())?,
-    )?;
-
-    Ok(())
-}
@@ -0,0 +1,383 @@
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 输出文件名模板支持的占位符
+const PLACEHOLDERS: [&str; 4] = ["title", "id", "date", "dirname"];
+
+/// 输出文件已存在时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OnConflict {
+    /// 覆盖已有文件
+    #[default]
+    Overwrite,
+    /// 报错, 不修改已有文件
+    Error,
+    /// 在文件名后追加 `-1`、`-2` 等序号
+    Suffix,
+}
+
+/// 渲染输出文件名时可用的值
+pub struct NameContext<'a> {
+    pub id: &'a str,
+    /// description.xml 中的中文标题, 缺失时使用编号
+    pub title: Option<&'a str>,
+    /// yyyymmdd
+    pub date: &'a str,
+    /// 锁屏包目录名
+    pub dirname: &'a str,
+}
+
+/// 检查输出文件名模板, 如 `{title}-{id}.itz`
+pub fn validate_output_name(template: &str) -> io::Result<()> {
+    render(template, |_| Some(String::new())).map(drop)
+}
+
+/// 按模板生成输出文件名, 占位符的值中不能用于文件名的字符替换为 `_`
+pub fn render_output_name(template: &str, context: &NameContext<'_>) -> io::Result<String> {
+    let name = render(template, |placeholder| {
+        let value = match placeholder {
+            "title" => context.title.unwrap_or(context.id),
+            "id" => context.id,
+            "date" => context.date,
+            "dirname" => context.dirname,
+            _ => return None,
+        };
+        Some(sanitize(value))
+    })?;
+
+    if name.trim().is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("无效的输出文件名 {name:?}（模板 {template:?}）"),
+        ));
+    }
+    Ok(name)
+}
+
+/// 不依赖编号与标题的模板在打包前即可确定文件名, 否则返回 None
+pub fn static_output_name(template: &str, date: &str, dirname: &str) -> Option<String> {
+    if template.contains("{id}") || template.contains("{title}") {
+        return None;
+    }
+    let context = NameContext {
+        id: "",
+        title: None,
+        date,
+        dirname,
+    };
+    render_output_name(template, &context).ok()
+}
+
+/// `{date}` 的值: 可复现构建时取固定时间戳的 UTC 日期, 否则为当天日期
+pub fn output_date(source_date_epoch: Option<i64>) -> String {
+    match source_date_epoch {
+        Some(timestamp) => DateTime::from_timestamp(timestamp, 0)
+            .unwrap_or_default()
+            .format("%Y%m%d")
+            .to_string(),
+        None => Local::now().format("%Y%m%d").to_string(),
+    }
+}
+
+/// 锁屏包目录名, `input` 为 `.` 等相对路径时取其绝对路径的目录名
+pub fn package_dirname(input: &Path) -> String {
+    input
+        .canonicalize()
+        .ok()
+        .as_deref()
+        .unwrap_or(input)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "lockscreen".to_string())
+}
+
+/// 未指定输出目录时使用的锁屏包上一级目录
+///
+/// `pkg` 的上一级为当前目录, `.` 与 `..` 的上一级为 `./..` 与 `../..`
+pub fn parent_output_dir(input: &Path) -> PathBuf {
+    match input.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ if input.file_name().is_some() => PathBuf::from("."),
+        _ => input.join(".."),
+    }
+}
+
+/// 按 `on_conflict` 确定 `dir` 下的输出路径, 不创建任何文件
+pub fn output_path(dir: &Path, name: &str, on_conflict: OnConflict) -> io::Result<PathBuf> {
+    let path = dir.join(name);
+    match on_conflict {
+        OnConflict::Overwrite => Ok(path),
+        OnConflict::Error if path.exists() => Err(already_exists(&path)),
+        OnConflict::Error => Ok(path),
+        OnConflict::Suffix => Ok(suffixed(dir, name)
+            .find(|path| !path.exists())
+            .unwrap_or(path)),
+    }
+}
+
+/// 与 [`output_path`] 相同, 但 [`OnConflict::Suffix`] 时立即创建空文件占用该路径,
+/// 避免并行打包的任务选中同一个文件名
+pub fn claim_output_path(dir: &Path, name: &str, on_conflict: OnConflict) -> io::Result<PathBuf> {
+    if on_conflict != OnConflict::Suffix {
+        return output_path(dir, name, on_conflict);
+    }
+    for path in suffixed(dir, name) {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => return Ok(path),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("序号无穷")
+}
+
+/// 放弃 [`claim_output_path`] 占用的路径
+pub fn release_output_path(path: &Path) {
+    if fs::metadata(path).is_ok_and(|metadata| metadata.len() == 0) {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// 将 `path` 现有的内容保留为 `<path>.bak`, 之前的 .bak 被替换; `path` 不存在时不做任何事
+///
+/// 优先使用硬链接, `path` 在新文件重命名到位之前保持不变。
+pub fn backup_output(path: &Path) -> io::Result<()> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+
+    match fs::remove_file(&backup) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    fs::hard_link(path, &backup).or_else(|_| fs::copy(path, &backup).map(drop))
+}
+
+/// 输出文件的进程间锁, drop 时释放
+pub struct OutputLock {
+    _file: File,
+}
+
+/// 锁定输出路径 `path`, 同一路径的其他 vlp 进程需等待锁释放后才能写入
+///
+/// 锁文件位于系统临时目录, 以 `path` 的 SHA-256 命名, 不会在输出目录中留下文件。
+pub fn lock_output(path: &Path) -> io::Result<OutputLock> {
+    let digest = Sha256::digest(path.as_os_str().as_encoded_bytes());
+    let name: String = digest[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    let lock_path = std::env::temp_dir().join(format!("vlp-{name}.lock"));
+
+    // 其他用户创建的锁文件可能不可写, 只读打开同样可以加锁
+    let file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(&lock_path)
+        .or_else(|_| File::open(&lock_path))?;
+    file.lock()?;
+    Ok(OutputLock { _file: file })
+}
+
+/// `name`、`name-1`、`name-2`…, 序号加在扩展名之前
+fn suffixed<'a>(dir: &'a Path, name: &'a str) -> impl Iterator<Item = PathBuf> + 'a {
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => (stem, Some(ext)),
+        _ => (name, None),
+    };
+    (0..).map(move |n| {
+        let name = match (n, ext) {
+            (0, _) => name.to_string(),
+            (n, Some(ext)) => format!("{stem}-{n}.{ext}"),
+            (n, None) => format!("{stem}-{n}"),
+        };
+        dir.join(name)
+    })
+}
+
+fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!(
+            "输出文件已存在: {}（使用 --force 覆盖或 --auto-suffix 自动改名）",
+            path.display()
+        ),
+    )
+}
+
+fn render(template: &str, mut value: impl FnMut(&str) -> Option<String>) -> io::Result<String> {
+    let invalid = |message: String| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("无效的输出文件名模板 {template:?}: {message}"),
+        )
+    };
+
+    let mut result = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| invalid("缺少 '}'".to_string()))?;
+        let placeholder = &rest[start + 1..start + end];
+        if !PLACEHOLDERS.contains(&placeholder) {
+            return Err(invalid(format!("未知的占位符 {{{placeholder}}}")));
+        }
+        result.push_str(&value(placeholder).unwrap_or_default());
+        rest = &rest[start + end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+fn sanitize(value: &str) -> String {
+    value
+        .trim()
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn context<'a>(title: Option<&'a str>) -> NameContext<'a> {
+        NameContext {
+            id: "20250101123",
+            title,
+            date: "20250101",
+            dirname: "spring",
+        }
+    }
+
+    #[test]
+    fn renders_all_placeholders() {
+        let name = render_output_name("{title}-{id}-{date}-{dirname}.itz", &context(Some("春天")))
+            .unwrap();
+        assert_eq!(name, "春天-20250101123-20250101-spring.itz");
+    }
+
+    #[test]
+    fn title_falls_back_to_id() {
+        assert_eq!(
+            render_output_name("{title}", &context(None)).unwrap(),
+            "20250101123"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        for template in ["{name}", "{title", "{}"] {
+            let err = validate_output_name(template).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{template}");
+        }
+        assert!(validate_output_name("lockscreen").is_ok());
+    }
+
+    #[test]
+    fn sanitizes_illegal_characters() {
+        let name = render_output_name("{title}.itz", &context(Some(" a/b\\c:d*e?f\"g<h>i|j\tk ")))
+            .unwrap();
+        assert_eq!(name, "a_b_c_d_e_f_g_h_i_j_k.itz");
+    }
+
+    #[test]
+    fn rejects_names_that_are_not_a_single_file_name() {
+        for title in ["", "  ", ".", ".."] {
+            let err = render_output_name("{title}", &context(Some(title))).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{title:?}");
+        }
+        assert!(render_output_name("a/{id}", &context(None)).is_err());
+    }
+
+    #[test]
+    fn static_name_only_without_id_and_title() {
+        assert_eq!(
+            static_output_name("{dirname}-{date}", "20250101", "spring").as_deref(),
+            Some("spring-20250101")
+        );
+        assert_eq!(static_output_name("{id}", "20250101", "spring"), None);
+        assert_eq!(
+            static_output_name("{title}.itz", "20250101", "spring"),
+            None
+        );
+    }
+
+    #[test]
+    fn suffix_is_inserted_before_extension() {
+        let dir = Path::new("out");
+        let names: Vec<PathBuf> = suffixed(dir, "lock.itz").take(3).collect();
+        assert_eq!(
+            names,
+            ["lock.itz", "lock-1.itz", "lock-2.itz"].map(|name| dir.join(name))
+        );
+        let names: Vec<PathBuf> = suffixed(dir, "lockscreen").take(3).collect();
+        assert_eq!(
+            names,
+            ["lockscreen", "lockscreen-1", "lockscreen-2"].map(|name| dir.join(name))
+        );
+        let names: Vec<PathBuf> = suffixed(dir, ".hidden").take(2).collect();
+        assert_eq!(names, [".hidden", ".hidden-1"].map(|name| dir.join(name)));
+    }
+
+    #[test]
+    fn suffix_policy_skips_existing_files() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        assert_eq!(
+            output_path(dir, "lockscreen", OnConflict::Suffix).unwrap(),
+            dir.join("lockscreen")
+        );
+
+        fs::write(dir.join("lockscreen"), "old").unwrap();
+        fs::write(dir.join("lockscreen-1"), "old").unwrap();
+        assert_eq!(
+            output_path(dir, "lockscreen", OnConflict::Suffix).unwrap(),
+            dir.join("lockscreen-2")
+        );
+
+        let claimed = claim_output_path(dir, "lockscreen", OnConflict::Suffix).unwrap();
+        assert_eq!(claimed, dir.join("lockscreen-2"));
+        assert_eq!(
+            claim_output_path(dir, "lockscreen", OnConflict::Suffix).unwrap(),
+            dir.join("lockscreen-3")
+        );
+        release_output_path(&claimed);
+        assert!(!claimed.exists());
+    }
+
+    #[test]
+    fn no_clobber_refuses_existing_file() {
+        let tmp = TempDir::new().unwrap();
+        let dir = tmp.path();
+        assert_eq!(
+            output_path(dir, "lockscreen", OnConflict::Error).unwrap(),
+            dir.join("lockscreen")
+        );
+
+        fs::write(dir.join("lockscreen"), "old").unwrap();
+        let err = claim_output_path(dir, "lockscreen", OnConflict::Error).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(dir.join("lockscreen")).unwrap(), "old");
+
+        assert_eq!(
+            output_path(dir, "lockscreen", OnConflict::Overwrite).unwrap(),
+            dir.join("lockscreen")
+        );
+    }
+}
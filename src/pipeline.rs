@@ -1,73 +1,520 @@
 use crate::{
-    archivefiles::{ArchiveError, ArchiveFile, ArchiveFiles},
-    utils::{deal_dscr_xml, generate_lockscreen_number},
-    zip_command::ZipCommand,
+    archivefiles::{ArchiveFile, ArchiveFiles},
+    archiver::Archiver,
+    compression::CompressionPolicy,
+    description::{DescriptionEdits, patch_description, read_id_and_title},
+    error::{ArchiveError, Stage},
+    exclude::{ExcludeRules, IGNORE_FILE_NAME},
+    id::{IdMode, IdPolicy},
+    lockfile::{LOCKFILE_NAME, PackageLock},
+    output::{
+        NameContext, OnConflict, backup_output, claim_output_path, lock_output, output_date,
+        package_dirname, release_output_path, render_output_name,
+    },
+    report::{Reporter, SilentReporter, StepProgress},
+    utils::{generate_lockscreen_number_at, sha256_file, validate_id},
 };
-use console::Emoji;
 use fs_extra::file::{CopyOptions as FileCopyOptions, copy as copy_file};
-use indicatif::{ProgressBar, ProgressStyle};
-use std::{path::Path, time::Duration};
+use serde::Serialize;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tempfile::TempDir;
+use walkdir::WalkDir;
+
+/// 默认的输出文件名
+pub const DEFAULT_OUTPUT_NAME: &str = "lockscreen";
+
+/// 打包选项
+#[derive(Clone)]
+pub struct PackOptions {
+    /// 指定锁屏编号, 优先于 vlp.lock 与自动生成的编号
+    pub id: Option<String>,
+    /// 未指定编号时编号的来源
+    pub id_mode: IdMode,
+    /// 需要新编号时的分配规则
+    pub ids: IdPolicy,
+    /// 输出文件名模板, 默认为 "lockscreen", 见 [`render_output_name`]
+    pub output_name: Option<String>,
+    /// 输出文件已存在时的处理方式
+    pub on_conflict: OnConflict,
+    /// 覆盖输出文件前将其保留为 `<输出文件>.bak`
+    pub backup: bool,
+    /// 可复现构建使用的固定时间戳
+    pub source_date_epoch: Option<i64>,
+    /// Step 2 中对 description.xml 的修改
+    pub description: DescriptionEdits,
+    /// 各条目的压缩策略
+    pub compression: CompressionPolicy,
+    /// 额外的排除规则（gitignore 语法）, 与内置规则及 .vlpignore 合并
+    pub exclude: Vec<String>,
+    /// 打包进度的输出方式, 批量打包时多个锁屏包共用
+    pub reporter: Arc<dyn Reporter>,
+}
+
+impl Default for PackOptions {
+    fn default() -> Self {
+        PackOptions {
+            id: None,
+            id_mode: IdMode::default(),
+            ids: IdPolicy::default(),
+            output_name: None,
+            on_conflict: OnConflict::default(),
+            backup: false,
+            source_date_epoch: None,
+            description: DescriptionEdits::default(),
+            compression: CompressionPolicy::default(),
+            exclude: Vec::new(),
+            reporter: Arc::new(SilentReporter),
+        }
+    }
+}
+
+impl PackOptions {
+    pub fn output_name(&self) -> &str {
+        self.output_name.as_deref().unwrap_or(DEFAULT_OUTPUT_NAME)
+    }
+}
+
+/// 打包结果
+#[derive(Debug, Clone)]
+pub struct PackOutput {
+    /// 写入 description.xml 的锁屏编号
+    pub id: String,
+    /// 修改后 description.xml 中的中文标题
+    pub title: Option<String>,
+    /// 最终输出的 lockscreen 文件路径
+    pub output: PathBuf,
+    /// 各层文件的大小
+    pub sizes: PackSizes,
+    /// 输出文件的 SHA-256（小写十六进制）
+    pub sha256: String,
+    /// Step 1-4 各自的耗时
+    pub steps: Vec<StepTiming>,
+    /// 被排除的文件（相对于锁屏包根目录）
+    pub excluded: Vec<PathBuf>,
+    /// 不影响打包结果的问题, 如预览图缺失
+    pub warnings: Vec<String>,
+}
+
+/// 打包过程中各层文件的大小（字节）
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PackSizes {
+    /// Step 1 生成的 lockscreen/<编号>.zip
+    pub lockscreen: u64,
+    /// Step 3 生成的 lockscreen.itz
+    pub itz: u64,
+    /// 最终输出的 lockscreen 文件
+    pub output: u64,
+}
+
+/// 单个步骤的耗时
+#[derive(Debug, Clone, Copy)]
+pub struct StepTiming {
+    pub stage: Stage,
+    pub duration: Duration,
+}
+
+/// 检查锁屏包是否包含打包所需的文件
+pub fn validate_package(input_path: &Path) -> Result<(), ArchiveError> {
+    let required = ["preview", "description.xml", "lockscreen/manifest.xml"];
+    let missing: Vec<String> = required
+        .iter()
+        .filter(|entry| !input_path.join(entry).exists())
+        .map(|entry| entry.to_string())
+        .collect();
 
-static SPARKLE: Emoji<'_, '_> = Emoji("✨ ", ":-)");
+    if !missing.is_empty() {
+        return Err(ArchiveError::MissingFiles {
+            package: input_path.to_path_buf(),
+            missing,
+        });
+    }
+
+    Ok(())
+}
 
 pub fn process_lockscreen_package(
     input: &Path,
     output: &Path,
-    zip_command: ZipCommand,
-) -> Result<(), ArchiveError> {
+    archiver: &dyn Archiver,
+    options: &PackOptions,
+) -> Result<PackOutput, ArchiveError> {
+    options.reporter.started(input);
+
+    // 输出目录不可用时在打包前失败; vlp.lock 与登记簿只在输出文件写入成功后更新, 失败时撤销预留的新编号
+    let result = create_output_dir(output).and_then(|output_dir| {
+        let resolved = resolve_lockscreen_id(input, output, options)
+            .map_err(|e| e.in_stage(Stage::Description))?;
+        match pack_steps(input, output, &output_dir, archiver, options, &resolved.id) {
+            Ok(packed) => resolved.commit(input, options).map(|_| packed),
+            Err(e) => {
+                resolved.abort(input, options);
+                Err(e)
+            }
+        }
+    });
+    match &result {
+        Ok(packed) => options.reporter.finished(input, packed),
+        Err(e) => options.reporter.failed(input, e),
+    }
+
+    result
+}
+
+/// [`resolve_lockscreen_id`] 确定的编号, 以及打包成功后需要写入 vlp.lock 与编号登记簿的记录
+#[derive(Debug)]
+pub struct ResolvedId {
+    pub id: String,
+    /// 打包成功后写入的 vlp.lock, 无需更新时为 None
+    lock: Option<PackageLock>,
+    /// 指定或沿用的编号, 打包成功后记入登记簿
+    register: bool,
+    /// 新分配的编号, 已在登记簿中预留
+    reserved: bool,
+}
+
+impl ResolvedId {
+    /// 打包成功后写入 vlp.lock, 并将指定或沿用的编号记入登记簿
+    pub fn commit(self, input: &Path, options: &PackOptions) -> Result<(), ArchiveError> {
+        if self.register {
+            options.ids.register(&self.id, input)?;
+        }
+        if let Some(lock) = self.lock {
+            lock.write(input)
+                .map_err(|e| ArchiveError::io_at(input.join(LOCKFILE_NAME), e))?;
+        }
+        Ok(())
+    }
+
+    /// 打包失败后撤销登记簿中预留的新编号; 撤销失败时编号仅被浪费, 不影响打包结果
+    pub fn abort(self, input: &Path, options: &PackOptions) {
+        if self.reserved {
+            let _ = options.ids.release(&self.id, input);
+        }
+    }
+}
+
+/// 确定本次打包使用的锁屏编号: 指定编号 > `--keep-id`/`--new-id` > vlp.lock > 自动分配
+///
+/// 首次分配的编号（以及 `--keep-id`/`--new-id` 更换后的编号）在打包成功后写入锁屏包的 vlp.lock, 之后的打包沿用该编号。
+/// 新分配的编号立即在登记簿中预留, 避免同时打包的进程分到相同的编号; 其余写入由 [`ResolvedId::commit`] 完成。
+/// 可复现构建的编号由时间戳推导, 不读写编号登记簿与 vlp.lock; 其余情况下指定或沿用的编号也会记入登记簿。
+pub fn resolve_lockscreen_id(
+    input: &Path,
+    output_dir: &Path,
+    options: &PackOptions,
+) -> Result<ResolvedId, ArchiveError> {
+    let lock_path = input.join(LOCKFILE_NAME);
+    let lock = PackageLock::read(input).map_err(|e| ArchiveError::io_at(&lock_path, e))?;
+
+    let existing = existing_id(input, options, lock.as_ref())?;
+    let reproducible = options.source_date_epoch.is_some();
+    let register = existing.is_some() && !reproducible;
+    let reserved = existing.is_none() && !(reproducible && options.id_mode != IdMode::New);
+    let id = match (existing, options.source_date_epoch) {
+        (Some(id), _) => id,
+        (None, Some(timestamp)) if options.id_mode != IdMode::New => {
+            generate_lockscreen_number_at(timestamp, &package_dirname(input))
+        }
+        (None, _) => options.ids.allocate(input, output_dir)?,
+    };
+
+    // 指定编号只用于本次打包, 不覆盖已有的 vlp.lock
+    let changed = lock
+        .as_ref()
+        .is_none_or(|lock| lock.id != id && options.id.is_none());
+    let lock = (changed && !reproducible).then(|| PackageLock { id: id.clone() });
+
+    Ok(ResolvedId {
+        id,
+        lock,
+        register,
+        reserved,
+    })
+}
+
+/// [`resolve_lockscreen_id`] 会使用的编号, 不写入 vlp.lock 与编号登记簿, 用于 `--dry-run`
+///
+/// 需要随机分配新编号时, 实际打包分配的编号可能与此不同。
+pub fn preview_lockscreen_id(
+    input: &Path,
+    output_dir: &Path,
+    options: &PackOptions,
+) -> Result<String, ArchiveError> {
+    let lock_path = input.join(LOCKFILE_NAME);
+    let lock = PackageLock::read(input).map_err(|e| ArchiveError::io_at(&lock_path, e))?;
+
+    Ok(
+        match (
+            existing_id(input, options, lock.as_ref())?,
+            options.source_date_epoch,
+        ) {
+            (Some(id), _) => id,
+            (None, Some(timestamp)) if options.id_mode != IdMode::New => {
+                generate_lockscreen_number_at(timestamp, &package_dirname(input))
+            }
+            (None, _) => options.ids.preview(output_dir)?,
+        },
+    )
+}
+
+/// 无需分配新编号时使用的编号: 指定编号、description.xml 中的编号或 vlp.lock 中的编号
+///
+/// 编号会拼接到中间文件与输出文件的路径中, 任一来源的编号都须通过 [`validate_id`]。
+fn existing_id(
+    input: &Path,
+    options: &PackOptions,
+    lock: Option<&PackageLock>,
+) -> Result<Option<String>, ArchiveError> {
+    let (id, source) = match (&options.id, options.id_mode) {
+        (Some(id), _) => (id.clone(), None),
+        (None, IdMode::KeepDescription) => {
+            (description_id(input)?, Some(input.join("description.xml")))
+        }
+        (None, IdMode::New) => return Ok(None),
+        (None, IdMode::Locked) => match lock {
+            Some(lock) => (lock.id.clone(), Some(input.join(LOCKFILE_NAME))),
+            None => return Ok(None),
+        },
+    };
+    validate_id(&id).map_err(|e| match source {
+        Some(path) => {
+            ArchiveError::InvalidPath(io::Error::new(e.kind(), format!("{}: {e}", path.display())))
+        }
+        None => ArchiveError::InvalidPath(e),
+    })?;
+    Ok(Some(id))
+}
+
+/// 源 description.xml 中已有的编号
+fn description_id(input: &Path) -> Result<String, ArchiveError> {
+    let path = input.join("description.xml");
+    let content = fs::read_to_string(&path).map_err(|e| ArchiveError::io_at(&path, e))?;
+    let (id, _) = read_id_and_title(&content);
+    id.filter(|id| !id.trim().is_empty())
+        .ok_or_else(|| ArchiveError::Description {
+            stage: None,
+            path,
+            source: io::Error::new(io::ErrorKind::InvalidData, "没有可沿用的 id 字段"),
+        })
+}
+
+/// 执行一个步骤并通知 reporter, 步骤中的错误记录为该阶段, 耗时追加到 `timings`
+fn run_step<T>(
+    input: &Path,
+    options: &PackOptions,
+    timings: &mut Vec<StepTiming>,
+    stage: Stage,
+    total_bytes: u64,
+    step: impl FnOnce() -> Result<T, ArchiveError>,
+) -> Result<T, ArchiveError> {
+    options.reporter.step_started(input, stage, total_bytes);
+    let started = Instant::now();
+    let result = step().map_err(|e| e.in_stage(stage))?;
+    timings.push(StepTiming {
+        stage,
+        duration: started.elapsed(),
+    });
+    options.reporter.step_finished(input, stage);
+    Ok(result)
+}
+
+/// `path` 下未被排除的文件的总大小
+fn source_size(path: &Path, excludes: &ExcludeRules) -> Result<u64, ArchiveError> {
+    let mut total = 0;
+    let walker = WalkDir::new(path)
+        .follow_links(true)
+        .into_iter()
+        .filter_entry(|entry| !excludes.is_excluded(entry.path(), entry.file_type().is_dir()));
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+/// 创建输出目录并返回其绝对路径
+fn create_output_dir(output: &Path) -> Result<PathBuf, ArchiveError> {
+    fs::create_dir_all(output)
+        .and_then(|_| output.canonicalize())
+        .map_err(|source| ArchiveError::OutputWrite {
+            stage: Some(Stage::Output),
+            path: output.to_path_buf(),
+            source,
+        })
+}
+
+/// `output` 为用户给出的输出目录, `output_dir` 为 [`create_output_dir`] 返回的绝对路径
+fn pack_steps(
+    input: &Path,
+    output: &Path,
+    output_dir: &Path,
+    archiver: &dyn Archiver,
+    options: &PackOptions,
+    id: &str,
+) -> Result<PackOutput, ArchiveError> {
+    let excludes = &ExcludeRules::load(input, &options.exclude).map_err(|e| {
+        ArchiveError::io_at(input.join(IGNORE_FILE_NAME), e).in_stage(Stage::Validate)
+    })?;
+    let progress = StepProgress::new(options.reporter.clone(), input);
+    let mut warnings = Vec::new();
+    let mut timings = Vec::new();
+
     let temp_stage_dir = TempDir::new()?;
     let temp_dist_dir = TempDir::new()?;
 
     let intermediate_lockscreen_zip = temp_stage_dir.path().join("lockscreen.zip");
     let final_itz_file = temp_dist_dir.path().join("lockscreen.itz");
 
-    // 添加打包动画
-    let spinner = ProgressBar::new_spinner();
-    let spinner_style = ProgressStyle::with_template("{spinner:.blue}{wide_msg}")
-        .unwrap()
-        .tick_strings(&["⢎ ", "⠎⠁", "⠊⠑", "⠈⠱", " ⡱", "⢀⡰", "⢄⡠", "⢆⡀", ""]);
-    spinner.enable_steady_tick(Duration::from_millis(80));
-    spinner.set_style(spinner_style);
-    spinner.set_message(format!(" 正在打包:{}...", input.display()));
-
     // Step 1: 打包 lockscreen 目录
-    ArchiveFile::new(None, &input.join("lockscreen"))?
-        .not_copy()
-        .zip_with(&zip_command, &intermediate_lockscreen_zip)?;
+    let lockscreen_dir = input.join("lockscreen");
+    let lockscreen_size = source_size(&lockscreen_dir, excludes)?;
+    run_step(
+        input,
+        options,
+        &mut timings,
+        Stage::Lockscreen,
+        lockscreen_size,
+        || {
+            ArchiveFile::new(None, &lockscreen_dir)?
+                .not_copy()
+                .exclude(excludes.clone())
+                .compression(options.compression.clone())
+                .progress(progress.clone())
+                .zip_with(archiver, &intermediate_lockscreen_zip)
+        },
+    )?;
 
     // Step 2: 修复 description.xml 文件
+    let source_description_file = input.join("description.xml");
     let fixed_description_file = temp_stage_dir.path().join("description.xml");
-    let lockscreen_version_number = generate_lockscreen_number();
-    copy_file(
-        input.join("description.xml"),
-        &fixed_description_file,
-        &FileCopyOptions::new(),
+    let description_size = fs::metadata(&source_description_file)?.len();
+    let (lockscreen_version_number, title) = run_step(
+        input,
+        options,
+        &mut timings,
+        Stage::Description,
+        description_size,
+        || {
+            copy_file(
+                &source_description_file,
+                &fixed_description_file,
+                &FileCopyOptions::new(),
+            )?;
+            patch_description(&fixed_description_file, id, &options.description).map_err(
+                |source| ArchiveError::Description {
+                    stage: None,
+                    path: source_description_file.clone(),
+                    source,
+                },
+            )?;
+            progress.advance(description_size);
+            let (_, title) = read_id_and_title(&fs::read_to_string(&fixed_description_file)?);
+            Ok((id.to_string(), title))
+        },
     )?;
-    deal_dscr_xml(&fixed_description_file, &lockscreen_version_number)?;
 
     // Step 3: 组装 .itz 文件
-    ArchiveFiles::new(vec![
-        ArchiveFile::new(
-            Some(format!("lockscreen/{lockscreen_version_number}.zip")),
-            &intermediate_lockscreen_zip,
-        )?,
-        ArchiveFile::new(None, &input.join("preview"))?,
-        ArchiveFile::new(None, &fixed_description_file)?,
-    ])?
-    .zip_and_rename(&zip_command, &final_itz_file)?;
-
-    // Step 4: 重新打包 .itz 文件, 并重命名为 "lockscreen"
-    ArchiveFile::new(None, &final_itz_file)?
-        .not_copy()
-        //zip_with 函数目前需要传入绝对路径
-        .zip_and_rename(&zip_command, output.canonicalize()?.join("lockscreen"))?;
-
-    spinner.finish_with_message(format!(
-        "{}输出路径: {}",
-        SPARKLE,
-        output.join("lockscreen").display()
-    ));
+    let preview_dir = input.join("preview");
+    let preview_size = source_size(&preview_dir, excludes)?;
+    if preview_size == 0 {
+        let warning = "preview 目录中没有预览图".to_string();
+        options.reporter.warning(input, &warning);
+        warnings.push(warning);
+    }
+    let itz_size = fs::metadata(&intermediate_lockscreen_zip)?.len()
+        + preview_size
+        + fs::metadata(&fixed_description_file)?.len();
+    run_step(input, options, &mut timings, Stage::Itz, itz_size, || {
+        ArchiveFiles::new(vec![
+            ArchiveFile::new(
+                Some(format!("lockscreen/{lockscreen_version_number}.zip")),
+                &intermediate_lockscreen_zip,
+            )?,
+            ArchiveFile::new(None, &preview_dir)?.exclude(excludes.clone()),
+            ArchiveFile::new(None, &fixed_description_file)?,
+        ])?
+        .compression(options.compression.clone())
+        .progress(progress.clone())
+        .zip_and_rename(archiver, &final_itz_file)
+    })?;
 
-    Ok(())
+    // Step 4: 重新打包 .itz 文件, 并按输出文件名模板命名
+    let output_name = render_output_name(
+        options.output_name(),
+        &NameContext {
+            id: &lockscreen_version_number,
+            title: title.as_deref(),
+            date: &output_date(options.source_date_epoch),
+            dirname: &package_dirname(input),
+        },
+    )
+    .map_err(ArchiveError::InvalidPath)?;
+    let output_write_error = |source| ArchiveError::OutputWrite {
+        stage: Some(Stage::Output),
+        path: output_dir.join(&output_name),
+        source,
+    };
+    // 持有到写入完成, 同时运行的 vlp 不会同时选中或写入同一个输出文件
+    let _output_lock = lock_output(&output_dir.join(&output_name)).map_err(output_write_error)?;
+    let final_output = claim_output_path(output_dir, &output_name, options.on_conflict)
+        .map_err(output_write_error)?;
+    if options.backup && options.on_conflict == OnConflict::Overwrite {
+        backup_output(&final_output).map_err(output_write_error)?;
+    }
+    let output_size = fs::metadata(&final_itz_file)?.len();
+    run_step(
+        input,
+        options,
+        &mut timings,
+        Stage::Output,
+        output_size,
+        || {
+            ArchiveFile::new(None, &final_itz_file)?
+                .not_copy()
+                .compression(options.compression.clone())
+                .progress(progress.clone())
+                //zip_with 函数目前需要传入绝对路径
+                .zip_and_rename(archiver, &final_output)
+                .map_err(|e| match e {
+                    // 读取中间文件之外的 IO 错误都发生在写入输出目录时
+                    ArchiveError::Io { path, source, .. }
+                        if path
+                            .as_deref()
+                            .is_none_or(|path| path.starts_with(output_dir)) =>
+                    {
+                        ArchiveError::OutputWrite {
+                            stage: None,
+                            path: final_output.clone(),
+                            source,
+                        }
+                    }
+                    e => e,
+                })
+                .inspect_err(|_| release_output_path(&final_output))
+        },
+    )?;
+
+    let sha256 = sha256_file(&final_output).map_err(|e| ArchiveError::io_at(&final_output, e))?;
+
+    Ok(PackOutput {
+        id: lockscreen_version_number,
+        title,
+        output: output.join(final_output.file_name().unwrap_or_default()),
+        sizes: PackSizes {
+            lockscreen: fs::metadata(&intermediate_lockscreen_zip)?.len(),
+            itz: output_size,
+            output: fs::metadata(&final_output)?.len(),
+        },
+        sha256,
+        steps: timings,
+        excluded: excludes.excluded(),
+        warnings,
+    })
 }
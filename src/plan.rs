@@ -0,0 +1,187 @@
+use crate::{
+    archiver::entry_name,
+    description::{patched_description, read_id_and_title},
+    error::{ArchiveError, Stage},
+    exclude::{ExcludeRules, IGNORE_FILE_NAME},
+    output::{NameContext, output_date, output_path, package_dirname, render_output_name},
+    pipeline::{PackOptions, preview_lockscreen_id, validate_package},
+};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// `--dry-run` 的结果: 打包时将要生成的文件及其嵌套的条目, 生成过程中不写入任何文件
+#[derive(Debug, Serialize)]
+pub struct PackPlan {
+    pub input: PathBuf,
+    /// 将使用的锁屏编号, 随机分配时实际打包的编号可能不同
+    pub id: String,
+    /// 修改后 description.xml 中的中文标题
+    pub title: Option<String>,
+    pub output: PathBuf,
+    /// 从最终输出文件开始逐层展开的条目, 与 [`inspect`](crate::inspect) 的输出顺序一致
+    pub entries: Vec<PlannedEntry>,
+    /// 被排除的文件（相对于锁屏包根目录）
+    pub excluded: Vec<PathBuf>,
+    pub warnings: Vec<String>,
+}
+
+/// 计划中的一个条目
+#[derive(Debug, Serialize)]
+pub struct PlannedEntry {
+    /// 条目在其所在压缩包中的名称, 最外层为输出文件路径
+    pub name: String,
+    /// 所在的嵌套层级, 0 表示输出文件本身
+    pub depth: usize,
+    /// 文件的原始大小; 嵌套压缩包为其中文件的原始大小之和
+    pub size: u64,
+}
+
+/// 校验锁屏包并计算打包计划: 确定编号与输出路径, 列出每一层压缩包的内容
+pub fn plan_lockscreen_package(
+    input: &Path,
+    output_dir: &Path,
+    options: &PackOptions,
+) -> Result<PackPlan, ArchiveError> {
+    validate_package(input)?;
+    let excludes = ExcludeRules::load(input, &options.exclude).map_err(|e| {
+        ArchiveError::io_at(input.join(IGNORE_FILE_NAME), e).in_stage(Stage::Validate)
+    })?;
+    let mut warnings = Vec::new();
+
+    let id = preview_lockscreen_id(input, output_dir, options)
+        .map_err(|e| e.in_stage(Stage::Description))?;
+    let description_file = input.join("description.xml");
+    let description = fs::read_to_string(&description_file)
+        .and_then(|content| patched_description(&content, &id, &options.description))
+        .map_err(|source| ArchiveError::Description {
+            stage: Some(Stage::Description),
+            path: description_file.clone(),
+            source,
+        })?;
+    let (_, title) = read_id_and_title(&description);
+
+    let lockscreen = list_files(&input.join("lockscreen"), &excludes, "")?;
+    let preview = list_files(&input.join("preview"), &excludes, "preview/")?;
+    if preview.is_empty() {
+        warnings.push("preview 目录中没有预览图".to_string());
+    }
+    if !output_dir.is_dir() {
+        warnings.push(format!(
+            "输出目录不存在, 打包时将自动创建: {}",
+            output_dir.display()
+        ));
+    }
+
+    let lockscreen_size: u64 = lockscreen.iter().map(|(_, size)| size).sum();
+    let preview_size: u64 = preview.iter().map(|(_, size)| size).sum();
+    let description_size = description.len() as u64;
+    let itz_size = description_size + lockscreen_size + preview_size;
+
+    let output_name = render_output_name(
+        options.output_name(),
+        &NameContext {
+            id: &id,
+            title: title.as_deref(),
+            date: &output_date(options.source_date_epoch),
+            dirname: &package_dirname(input),
+        },
+    )
+    .map_err(ArchiveError::InvalidPath)?;
+    let output = output_path(output_dir, &output_name, options.on_conflict).map_err(|source| {
+        ArchiveError::OutputWrite {
+            stage: Some(Stage::Output),
+            path: output_dir.join(&output_name),
+            source,
+        }
+    })?;
+    let mut entries = vec![
+        PlannedEntry {
+            name: output.display().to_string(),
+            depth: 0,
+            size: itz_size,
+        },
+        PlannedEntry {
+            name: "lockscreen.itz".to_string(),
+            depth: 1,
+            size: itz_size,
+        },
+        PlannedEntry {
+            name: "description.xml".to_string(),
+            depth: 2,
+            size: description_size,
+        },
+        PlannedEntry {
+            name: format!("lockscreen/{id}.zip"),
+            depth: 2,
+            size: lockscreen_size,
+        },
+    ];
+    let files = |depth| move |(name, size)| PlannedEntry { name, depth, size };
+    entries.extend(lockscreen.into_iter().map(files(3)));
+    entries.extend(preview.into_iter().map(files(2)));
+
+    Ok(PackPlan {
+        input: input.to_path_buf(),
+        id,
+        title,
+        output,
+        entries,
+        excluded: excludes.excluded(),
+        warnings,
+    })
+}
+
+/// `dir` 下未被排除的文件, 按压缩时的顺序返回 (`prefix` + 条目名, 大小)
+fn list_files(
+    dir: &Path,
+    excludes: &ExcludeRules,
+    prefix: &str,
+) -> Result<Vec<(String, u64)>, ArchiveError> {
+    let mut files = Vec::new();
+    let walker = WalkDir::new(dir)
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| !excludes.is_excluded(entry.path(), entry.file_type().is_dir()));
+    for entry in walker {
+        let entry = entry?;
+        if entry.file_type().is_file() {
+            let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+            files.push((
+                format!("{prefix}{}", entry_name(relative)),
+                entry.metadata()?.len(),
+            ));
+        }
+    }
+    Ok(files)
+}
+
+impl fmt::Display for PackPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "锁屏包: {}", self.input.display())?;
+        writeln!(f, "编号: {}", self.id)?;
+        writeln!(f, "标题: {}", self.title.as_deref().unwrap_or("-"))?;
+        writeln!(f, "输出路径: {}", self.output.display())?;
+
+        writeln!(f, "\n{:>12}  条目", "原始大小")?;
+        for entry in &self.entries {
+            let indent = "  ".repeat(entry.depth);
+            writeln!(f, "{:>12}  {indent}{}", entry.size, entry.name)?;
+        }
+
+        if !self.excluded.is_empty() {
+            writeln!(f, "\n已排除:")?;
+            for path in &self.excluded {
+                writeln!(f, "  {}", path.display())?;
+            }
+        }
+        for warning in &self.warnings {
+            writeln!(f, "\n警告: {warning}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    error::{ArchiveError, Stage},
+    pipeline::{PackOutput, PackSizes},
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// 单个锁屏包的打包记录, 成功与失败使用同一结构, 供 `--json` 与 `--result-file` 输出
+///
+/// 失败时 `output`、`id` 等打包结果字段为 `null`, `error` 描述失败原因。
+#[derive(Debug, Serialize)]
+pub struct BuildRecord {
+    pub input: PathBuf,
+    /// `"ok"` 或 `"error"`
+    pub status: &'static str,
+    pub output: Option<PathBuf>,
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub sizes: Option<PackSizes>,
+    pub sha256: Option<String>,
+    pub steps: Vec<StepRecord>,
+    pub warnings: Vec<String>,
+    pub excluded: Vec<PathBuf>,
+    pub error: Option<ErrorRecord>,
+}
+
+/// 单个步骤的耗时
+#[derive(Debug, Serialize)]
+pub struct StepRecord {
+    pub stage: Stage,
+    pub duration_ms: f64,
+}
+
+/// 打包失败的原因
+#[derive(Debug, Serialize)]
+pub struct ErrorRecord {
+    /// 错误类型, 见 [`ArchiveError::kind`]
+    pub kind: &'static str,
+    pub stage: Option<Stage>,
+    pub message: String,
+    /// 单独打包该锁屏包时的退出码
+    pub exit_code: i32,
+}
+
+impl BuildRecord {
+    pub fn new(input: &Path, result: &Result<PackOutput, ArchiveError>) -> Self {
+        match result {
+            Ok(packed) => BuildRecord {
+                input: input.to_path_buf(),
+                status: "ok",
+                output: Some(packed.output.clone()),
+                id: Some(packed.id.clone()),
+                title: packed.title.clone(),
+                sizes: Some(packed.sizes),
+                sha256: Some(packed.sha256.clone()),
+                steps: packed
+                    .steps
+                    .iter()
+                    .map(|step| StepRecord {
+                        stage: step.stage,
+                        duration_ms: step.duration.as_secs_f64() * 1000.0,
+                    })
+                    .collect(),
+                warnings: packed.warnings.clone(),
+                excluded: packed.excluded.clone(),
+                error: None,
+            },
+            Err(e) => BuildRecord {
+                input: input.to_path_buf(),
+                status: "error",
+                output: None,
+                id: None,
+                title: None,
+                sizes: None,
+                sha256: None,
+                steps: Vec::new(),
+                warnings: Vec::new(),
+                excluded: Vec::new(),
+                error: Some(ErrorRecord {
+                    kind: e.kind(),
+                    stage: e.stage(),
+                    message: e.to_string(),
+                    exit_code: e.exit_code(),
+                }),
+            },
+        }
+    }
+}
@@ -1,28 +1,48 @@
 use std::io;
 
+#[cfg(target_os = "linux")]
+use linux_file_manager::{register_linux_file_managers, unregister_linux_file_managers};
 #[cfg(windows)]
 use win_right_click::{register_windows_right_click, unregister_windows_right_click};
 
+/// 注册右键菜单, 返回写入的注册表项或文件; `dry_run` 时只列出而不写入
 #[cfg(windows)]
-pub fn do_register() -> io::Result<()> {
-    register_windows_right_click()
+pub fn do_register(dry_run: bool) -> io::Result<Vec<String>> {
+    if !dry_run {
+        register_windows_right_click()?;
+    }
+    Ok(vec![win_right_click::KEY_PATH.to_string()])
+}
+
+#[cfg(target_os = "linux")]
+pub fn do_register(dry_run: bool) -> io::Result<Vec<String>> {
+    register_linux_file_managers(dry_run)
 }
 
-#[cfg(not(windows))]
-pub fn do_register() -> io::Result<()> {
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn do_register(_dry_run: bool) -> io::Result<Vec<String>> {
     Err(io::Error::new(
         io::ErrorKind::Unsupported,
         "当前平台不支持注册右键菜单",
     ))
 }
 
+/// 取消注册右键菜单, 返回删除的注册表项或文件; `dry_run` 时只列出而不删除
 #[cfg(windows)]
-pub fn do_unregister() -> io::Result<()> {
-    unregister_windows_right_click()
+pub fn do_unregister(dry_run: bool) -> io::Result<Vec<String>> {
+    if !dry_run {
+        unregister_windows_right_click()?;
+    }
+    Ok(vec![win_right_click::KEY_PATH.to_string()])
+}
+
+#[cfg(target_os = "linux")]
+pub fn do_unregister(dry_run: bool) -> io::Result<Vec<String>> {
+    unregister_linux_file_managers(dry_run)
 }
 
-#[cfg(not(windows))]
-pub fn do_unregister() -> io::Result<()> {
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn do_unregister(_dry_run: bool) -> io::Result<Vec<String>> {
     Err(io::Error::new(
         io::ErrorKind::Unsupported,
         "当前平台不支持取消注册右键菜单",
@@ -37,6 +57,8 @@ mod win_right_click {
     use winreg::RegKey;
     use winreg::enums::*;
 
+    pub const KEY_PATH: &str = "HKEY_CLASSES_ROOT\\Directory\\shell\\vlp";
+
     pub fn register_windows_right_click() -> io::Result<()> {
         let hkcr = RegKey::predef(HKEY_CLASSES_ROOT);
         let shell = hkcr.open_subkey_with_flags("Directory\\shell", KEY_WRITE)?;
@@ -63,3 +85,237 @@ mod win_right_click {
         Ok(())
     }
 }
+
+/// 在用户的 XDG 数据目录下安装各文件管理器的右键菜单项:
+/// Nautilus 脚本、Nemo 动作与 Dolphin 服务菜单。不关联目录类型, 不影响双击打开文件夹。
+/// 安装的文件记录在清单中, 取消注册时只删除清单中的文件。
+#[cfg(target_os = "linux")]
+mod linux_file_manager {
+    use std::env;
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::{Path, PathBuf};
+    use vivo_lockscreen_packing::utils::user_data_dir;
+
+    const MENU_NAME: &str = "打包vivo锁屏包";
+
+    /// 要安装的文件: 绝对路径、内容与是否可执行
+    struct Entry {
+        path: PathBuf,
+        content: String,
+        executable: bool,
+    }
+
+    fn data_dir() -> io::Result<PathBuf> {
+        user_data_dir().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "无法确定用户数据目录, 请设置 HOME 或 XDG_DATA_HOME",
+            )
+        })
+    }
+
+    /// 记录已安装文件的清单, 每行一个绝对路径
+    fn manifest_path(data_dir: &Path) -> PathBuf {
+        data_dir.join("vlp").join("registered-files")
+    }
+
+    fn entries(data_dir: &Path, exe: &Path) -> Vec<Entry> {
+        // 由文件管理器启动时没有终端, 结果通过桌面通知显示
+        let launcher = data_dir.join("vlp").join("vlp-pack.sh");
+        let script = format!(
+            r#"#!/bin/sh
+# 由 vlp register 生成, vlp unregister 时删除
+output=$({exe} --no-pause --no-color "$@" 2>&1)
+status=$?
+if command -v notify-send >/dev/null 2>&1; then
+    if [ "$status" -eq 0 ]; then
+        notify-send "{MENU_NAME}" "打包完成"
+    else
+        notify-send -u critical "{MENU_NAME}" "$output"
+    fi
+fi
+exit "$status"
+"#,
+            exe = shell_quote(exe)
+        );
+
+        let mut entries = vec![
+            Entry {
+                path: launcher.clone(),
+                content: script.clone(),
+                executable: true,
+            },
+            Entry {
+                path: data_dir.join("nautilus").join("scripts").join(MENU_NAME),
+                content: script,
+                executable: true,
+            },
+            Entry {
+                path: data_dir
+                    .join("nemo")
+                    .join("actions")
+                    .join("vlp.nemo_action"),
+                content: format!(
+                    "[Nemo Action]\n\
+                     Name={MENU_NAME}\n\
+                     Comment=使用 vlp 打包选中的锁屏包\n\
+                     Exec={} %F\n\
+                     Icon-Name=package-x-generic\n\
+                     Selection=notnone\n\
+                     Extensions=dir;\n",
+                    desktop_quote(&launcher)
+                ),
+                executable: false,
+            },
+        ];
+        // Dolphin 服务菜单: Plasma 6 读取 kio/servicemenus, Plasma 5 读取 kservices5/ServiceMenus
+        let service_menu = format!(
+            "[Desktop Entry]\n\
+             Type=Service\n\
+             MimeType=inode/directory;\n\
+             Actions=vlp;\n\
+             X-KDE-ServiceTypes=KonqPopupMenu/Plugin\n\
+             \n\
+             [Desktop Action vlp]\n\
+             Name={MENU_NAME}\n\
+             Icon=package-x-generic\n\
+             Exec={} %F\n",
+            desktop_quote(&launcher)
+        );
+        for dir in [
+            data_dir.join("kio").join("servicemenus"),
+            data_dir.join("kservices5").join("ServiceMenus"),
+        ] {
+            entries.push(Entry {
+                path: dir.join("vlp.desktop"),
+                content: service_menu.clone(),
+                // Dolphin 只加载可执行的服务菜单
+                executable: true,
+            });
+        }
+        entries
+    }
+
+    pub fn register_linux_file_managers(dry_run: bool) -> io::Result<Vec<String>> {
+        let data_dir = data_dir()?;
+        let exe = env::current_exe()?.canonicalize()?;
+        let entries = entries(&data_dir, &exe);
+        let manifest = manifest_path(&data_dir);
+
+        let mut files: Vec<PathBuf> = entries.iter().map(|entry| entry.path.clone()).collect();
+        files.push(manifest.clone());
+        if dry_run {
+            return Ok(display(&files));
+        }
+
+        // 重新注册时先移除上次安装的文件, 清单始终与实际安装的文件一致
+        if manifest.exists() {
+            remove_installed(&manifest)?;
+        }
+        let mut installed = Vec::new();
+        for entry in &entries {
+            let result = install(entry);
+            if result.is_ok() {
+                installed.push(entry.path.clone());
+            }
+            // 部分文件写入失败时同样记录已写入的文件, 以便取消注册
+            write_manifest(&manifest, &installed)?;
+            result
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", entry.path.display())))?;
+        }
+
+        Ok(display(&files))
+    }
+
+    pub fn unregister_linux_file_managers(dry_run: bool) -> io::Result<Vec<String>> {
+        let manifest = manifest_path(&data_dir()?);
+        let mut files = read_manifest(&manifest)?;
+        files.push(manifest.clone());
+        if !dry_run {
+            remove_installed(&manifest)?;
+        }
+        Ok(display(&files))
+    }
+
+    fn install(entry: &Entry) -> io::Result<()> {
+        if let Some(parent) = entry.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&entry.path, &entry.content)?;
+        let mode = if entry.executable { 0o755 } else { 0o644 };
+        fs::set_permissions(&entry.path, fs::Permissions::from_mode(mode))
+    }
+
+    fn read_manifest(manifest: &Path) -> io::Result<Vec<PathBuf>> {
+        match fs::read_to_string(manifest) {
+            Ok(content) => Ok(content
+                .lines()
+                .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+                .map(PathBuf::from)
+                .collect()),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("未找到注册记录 {}, 右键功能尚未注册", manifest.display()),
+            )),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn write_manifest(manifest: &Path, files: &[PathBuf]) -> io::Result<()> {
+        if let Some(parent) = manifest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut content = String::from("# 由 vlp register 生成, vlp unregister 时删除以下文件\n");
+        for file in files {
+            content.push_str(&format!("{}\n", file.display()));
+        }
+        fs::write(manifest, content)
+    }
+
+    /// 删除清单中记录的文件及清单本身, 已不存在的文件忽略
+    fn remove_installed(manifest: &Path) -> io::Result<()> {
+        for file in read_manifest(manifest)?
+            .iter()
+            .map(PathBuf::as_path)
+            .chain([manifest])
+        {
+            match fs::remove_file(file) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(io::Error::new(e.kind(), format!("{}: {e}", file.display())));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn display(files: &[PathBuf]) -> Vec<String> {
+        files
+            .iter()
+            .map(|file| file.display().to_string())
+            .collect()
+    }
+
+    /// 按 POSIX shell 规则用单引号包裹
+    fn shell_quote(path: &Path) -> String {
+        format!("'{}'", path.to_string_lossy().replace('\'', r"'\''"))
+    }
+
+    /// 按桌面文件 Exec 键的规则用双引号包裹
+    fn desktop_quote(path: &Path) -> String {
+        let mut quoted = String::from("\"");
+        for c in path.to_string_lossy().chars() {
+            if matches!(c, '"' | '`' | '$' | '\\') {
+                quoted.push('\\');
+            }
+            if c == '%' {
+                quoted.push('%');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    }
+}
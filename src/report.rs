@@ -0,0 +1,190 @@
+use crate::error::{ArchiveError, Stage};
+use crate::pipeline::PackOutput;
+use console::{Emoji, style};
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+static SPARKLE: Emoji<'_, '_> = Emoji("✨ ", ":-)");
+static WARNING: Emoji<'_, '_> = Emoji("⚠️ ", "WARN ");
+
+/// 打包进度的输出方式, 批量打包时会被多个线程同时调用
+///
+/// 每个锁屏包依次收到 `started`、若干组 `step_started`/`step_progress`/`step_finished`,
+/// 最后是 `finished` 或 `failed`; 不同锁屏包的事件可能交错, 以 `input` 区分。
+pub trait Reporter: Send + Sync {
+    /// 开始打包 `input`
+    fn started(&self, _input: &Path) {}
+
+    /// 开始执行某一步, `total_bytes` 为该步需要读取的源文件大小
+    fn step_started(&self, _input: &Path, _stage: Stage, _total_bytes: u64) {}
+
+    /// 当前步骤又处理了 `bytes` 字节
+    fn step_progress(&self, _input: &Path, _bytes: u64) {}
+
+    /// 某一步执行完成
+    fn step_finished(&self, _input: &Path, _stage: Stage) {}
+
+    /// 不影响打包结果的问题
+    fn warning(&self, _input: &Path, _message: &str) {}
+
+    /// `input` 打包完成
+    fn finished(&self, _input: &Path, _output: &PackOutput) {}
+
+    /// `input` 打包失败, 错误同时会作为返回值交给调用方
+    fn failed(&self, _input: &Path, _error: &ArchiveError) {}
+}
+
+/// 将压缩进度转发给某个锁屏包的 [`Reporter`], 由 `ArchiveFile`/`ArchiveFiles` 传给压缩后端
+#[derive(Clone)]
+pub struct StepProgress {
+    reporter: Arc<dyn Reporter>,
+    input: PathBuf,
+}
+
+impl StepProgress {
+    pub fn new(reporter: Arc<dyn Reporter>, input: &Path) -> Self {
+        StepProgress {
+            reporter,
+            input: input.to_path_buf(),
+        }
+    }
+
+    pub fn advance(&self, bytes: u64) {
+        self.reporter.step_progress(&self.input, bytes);
+    }
+}
+
+/// 不输出任何内容, 作为库使用时的默认值, 也用于 `--quiet`
+#[derive(Debug, Default)]
+pub struct SilentReporter;
+
+impl Reporter for SilentReporter {}
+
+/// 终端进度条, 每个锁屏包一行, 显示当前步骤已压缩的字节数
+pub struct ProgressReporter {
+    progress: MultiProgress,
+    /// 是否列出被排除的文件
+    verbose: bool,
+    bars: Mutex<HashMap<PathBuf, ProgressBar>>,
+}
+
+impl ProgressReporter {
+    pub fn new(verbose: bool) -> Self {
+        ProgressReporter {
+            progress: MultiProgress::new(),
+            verbose,
+            bars: Mutex::default(),
+        }
+    }
+
+    fn bar(&self, input: &Path) -> Option<ProgressBar> {
+        self.bars.lock().unwrap().get(input).cloned()
+    }
+
+    fn take_bar(&self, input: &Path) -> Option<ProgressBar> {
+        self.bars.lock().unwrap().remove(input)
+    }
+}
+
+impl Reporter for ProgressReporter {
+    fn started(&self, input: &Path) {
+        let bar = self.progress.add(ProgressBar::new_spinner());
+        let style = ProgressStyle::with_template(
+            "{spinner:.blue}{prefix} {msg} {wide_bar:.cyan/blue} {bytes}/{total_bytes}",
+        )
+        .unwrap()
+        .tick_strings(&["⢎ ", "⠎⠁", "⠊⠑", "⠈⠱", " ⡱", "⢀⡰", "⢄⡠", "⢆⡀", ""])
+        .progress_chars("=> ");
+        bar.enable_steady_tick(Duration::from_millis(80));
+        bar.set_style(style);
+        bar.set_prefix(format!(" 正在打包:{}", input.display()));
+
+        self.bars.lock().unwrap().insert(input.to_path_buf(), bar);
+    }
+
+    fn step_started(&self, input: &Path, stage: Stage, total_bytes: u64) {
+        if let Some(bar) = self.bar(input) {
+            bar.set_length(total_bytes);
+            bar.set_position(0);
+            bar.set_message(stage.to_string());
+        }
+    }
+
+    fn step_progress(&self, input: &Path, bytes: u64) {
+        if let Some(bar) = self.bar(input) {
+            bar.inc(bytes);
+        }
+    }
+
+    fn warning(&self, input: &Path, message: &str) {
+        self.progress.suspend(|| {
+            eprintln!(
+                "{WARNING}{} {message}",
+                style(format!("{}:", input.display())).yellow()
+            )
+        });
+    }
+
+    fn finished(&self, input: &Path, output: &PackOutput) {
+        if let Some(bar) = self.take_bar(input) {
+            bar.set_style(ProgressStyle::with_template("{msg}").unwrap());
+            bar.finish_with_message(format!("{}输出路径: {}", SPARKLE, output.output.display()));
+        }
+        if self.verbose {
+            self.progress.suspend(|| {
+                for path in &output.excluded {
+                    println!("  已排除: {}", input.join(path).display());
+                }
+            });
+        }
+    }
+
+    fn failed(&self, input: &Path, _error: &ArchiveError) {
+        if let Some(bar) = self.take_bar(input) {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// 逐行输出, 不含动画与控制字符, 用于重定向或 CI 等非交互环境
+pub struct PlainReporter {
+    /// 是否列出被排除的文件
+    verbose: bool,
+}
+
+impl PlainReporter {
+    pub fn new(verbose: bool) -> Self {
+        PlainReporter { verbose }
+    }
+}
+
+impl Reporter for PlainReporter {
+    fn started(&self, input: &Path) {
+        println!("{}: 开始打包", input.display());
+    }
+
+    fn step_started(&self, input: &Path, stage: Stage, total_bytes: u64) {
+        println!("{}: {stage} ({})", input.display(), HumanBytes(total_bytes));
+    }
+
+    fn warning(&self, input: &Path, message: &str) {
+        eprintln!("{}: 警告: {message}", input.display());
+    }
+
+    fn finished(&self, input: &Path, output: &PackOutput) {
+        if self.verbose {
+            for path in &output.excluded {
+                println!("{}: 已排除: {}", input.display(), path.display());
+            }
+        }
+        println!(
+            "{}: 输出路径: {} ({})",
+            input.display(),
+            output.output.display(),
+            HumanBytes(output.sizes.output)
+        );
+    }
+}
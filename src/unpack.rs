@@ -0,0 +1,125 @@
+use crate::{
+    config::CONFIG_FILE_NAME,
+    description::read_id_and_title,
+    error::ArchiveError,
+    inspect::{open_zip, read_capped},
+    lockfile::PackageLock,
+    utils::validate_id,
+};
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// 将打包好的 lockscreen 文件还原为可编辑的锁屏包目录, 返回还原后的目录
+///
+/// 依次逆转 `process_lockscreen_package` 的四个步骤:
+/// lockscreen → lockscreen.itz → (lockscreen/<id>.zip, preview/, description.xml),
+/// 并在目录中写入 `vlp.lock` 记录原编号、写入 `vlp.toml` 保留原标题, 以便重新打包时还原。
+pub fn unpack_lockscreen_package(input: &Path, output: &Path) -> Result<PathBuf, ArchiveError> {
+    // Step 4: 解开外层 zip, 取出 lockscreen.itz
+    let mut outer = open_zip(input)?;
+    let mut itz = ZipArchive::new(read_entry(&mut outer, "lockscreen.itz")?)
+        .map_err(|e| invalid_package(input, &format!("lockscreen.itz 不是有效的 zip: {e}")))?;
+
+    // Step 3: 在 .itz 中定位 lockscreen/<id>.zip
+    let nested_zip_name = (0..itz.len())
+        .filter_map(|i| itz.name_for_index(i))
+        .find(|name| is_nested_lockscreen_zip(name))
+        .map(str::to_string)
+        .ok_or_else(|| invalid_package(input, "lockscreen.itz 中缺少 lockscreen/<id>.zip"))?;
+    let zip_id = nested_zip_name
+        .trim_start_matches("lockscreen/")
+        .trim_end_matches(".zip")
+        .to_string();
+
+    // Step 2: 读取 description.xml 中的编号
+    let description = read_capped(
+        itz.by_name("description.xml")
+            .map_err(|_| invalid_package(input, "lockscreen.itz 中缺少 description.xml"))?,
+        "description.xml",
+    )?;
+    let description = String::from_utf8(description)
+        .map_err(|_| invalid_package(input, "description.xml 不是有效的 UTF-8 文本"))?;
+    let (xml_id, _) = read_id_and_title(&description);
+    let id = xml_id.unwrap_or(zip_id);
+    // 编号来自压缩包, 用作目录名前必须确认不会跳出输出目录
+    validate_id(&id).map_err(|e| invalid_package(input, &e.to_string()))?;
+
+    let target = output.join(&id);
+    if target.exists() && fs::read_dir(&target)?.next().is_some() {
+        return Err(ArchiveError::InvalidPath(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("目标目录已存在且不为空: {}", target.display()),
+        )));
+    }
+    fs::create_dir_all(&target)?;
+
+    // preview/ 与 description.xml 原样解压
+    for i in 0..itz.len() {
+        let mut entry = itz.by_index(i)?;
+        if entry.name() == nested_zip_name {
+            continue;
+        }
+        let relative = entry
+            .enclosed_name()
+            .ok_or_else(|| invalid_package(input, &format!("非法的条目路径: {}", entry.name())))?;
+        extract_entry(&mut entry, &target.join(relative))?;
+    }
+
+    // Step 1: 解开 lockscreen/<id>.zip 到 lockscreen 目录
+    let mut lockscreen = ZipArchive::new(read_entry(&mut itz, &nested_zip_name)?)
+        .map_err(|e| invalid_package(input, &format!("{nested_zip_name} 不是有效的 zip: {e}")))?;
+    lockscreen.extract(target.join("lockscreen"))?;
+
+    PackageLock { id: id.clone() }.write(&target)?;
+    // description.xml 中已是打包时写入的标题, 重新打包时原样保留
+    fs::write(target.join(CONFIG_FILE_NAME), RESTORED_CONFIG)?;
+
+    Ok(target)
+}
+
+const RESTORED_CONFIG: &str = "# 由 vlp unpack 生成, 重新打包时保留 description.xml 中的标题\n\
+[description]\n\
+title_strategy = \"keep\"\n";
+
+fn is_nested_lockscreen_zip(name: &str) -> bool {
+    name.strip_prefix("lockscreen/")
+        .and_then(|rest| rest.strip_suffix(".zip"))
+        .is_some_and(|id| !id.is_empty() && !id.contains('/'))
+}
+
+fn read_entry<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    name: &str,
+) -> Result<Cursor<Vec<u8>>, ArchiveError> {
+    let mut entry = archive.by_name(name).map_err(|_| {
+        ArchiveError::InvalidPath(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("压缩包中缺少 {name}"),
+        ))
+    })?;
+    Ok(Cursor::new(read_capped(&mut entry, name)?))
+}
+
+fn extract_entry<R: Read>(
+    entry: &mut zip::read::ZipFile<'_, R>,
+    target: &Path,
+) -> Result<(), ArchiveError> {
+    if entry.is_dir() {
+        fs::create_dir_all(target)?;
+        return Ok(());
+    }
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    io::copy(entry, &mut File::create(target)?)?;
+    Ok(())
+}
+
+fn invalid_package(input: &Path, reason: &str) -> ArchiveError {
+    ArchiveError::InvalidPath(io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("无效的锁屏文件 {}: {reason}", input.display()),
+    ))
+}
@@ -1,54 +1,161 @@
-use std::fs;
-use std::io::Write;
-use std::{io, path::Path};
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 
-use chrono::Local;
-use rand::rngs::StdRng;
-use rand::{Rng, SeedableRng};
+use chrono::DateTime;
+use sha2::{Digest, Sha256};
 
-/// 生成锁屏编号, yyyymmdd+xxx
-pub fn generate_lockscreen_number() -> String {
-    let seed = Local::now().timestamp() as u64;
-    let mut rng = StdRng::seed_from_u64(seed);
-    let randnum: u32 = rng.random_range(100..=998);
-    let date = Local::now().format("%Y%m%d").to_string();
+/// 根据固定时间戳与锁屏包的标识（如目录名）生成锁屏编号, 用于可复现构建
+///
+/// 日期取 UTC 时间, 序号由时间戳与 `package` 的 SHA-256 推导, 不依赖随机数生成器的实现细节;
+/// 同一次构建（时间戳相同）中的不同锁屏包因此得到不同的序号。
+pub fn generate_lockscreen_number_at(timestamp: i64, package: &str) -> String {
+    let date = DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%Y%m%d");
+    let mut hasher = Sha256::new();
+    hasher.update(timestamp.to_le_bytes());
+    hasher.update(package.as_bytes());
+    let digest = hasher.finalize();
+    let hash = u64::from_le_bytes(digest[..8].try_into().unwrap());
+    let seq = 100 + hash % 900;
 
-    format!("{date}{randnum}")
+    format!("{date}{seq}")
 }
 
-// 修改 description.xml 中的 ID 和 title
-pub fn deal_dscr_xml<P: AsRef<Path>>(description_file: P, new_number: &str) -> io::Result<()> {
-    let path = description_file.as_ref();
-    let content = fs::read_to_string(path)?;
+/// 检查锁屏编号能否安全地用作文件名: 只能由 ASCII 字母、数字、`_` 与 `-` 组成
+///
+/// 编号会拼接到 `lockscreen/<id>.zip` 与还原目录等路径中,
+/// 含有 `..`、路径分隔符或盘符的编号可能写到预期目录之外。
+pub fn validate_id(id: &str) -> io::Result<()> {
+    let valid = !id.is_empty()
+        && id
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_' || byte == b'-');
+    if valid {
+        return Ok(());
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("无效的锁屏编号 {id:?}: 只能包含字母、数字、_ 与 -"),
+    ))
+}
 
-    let updated = replace_id_and_title(&content, new_number)
-        .ok_or_else(|| io::Error::other("id and title replacements must both succeed"))?;
+/// 用户数据目录: Windows 上为 `%APPDATA%`, 其他平台为 `$XDG_DATA_HOME`（默认 `~/.local/share`）
+pub fn user_data_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        std::env::var_os("XDG_DATA_HOME")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")))
+    }
+}
 
-    fs::write(path, updated)?;
-    Ok(())
+/// 计算文件的 SHA-256, 返回小写十六进制字符串
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
 }
 
-pub fn pause_before_exit() {
-    eprint!("按回车键退出...");
-    let _ = std::io::stdout().flush();
-    let _ = std::io::stdin().read_line(&mut String::new());
+/// 出错退出前是否等待用户按回车
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PauseMode {
+    /// 仅在通过拖拽或右键菜单启动（独占控制台窗口）时等待, 避免窗口一闪而过
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl PauseMode {
+    pub fn should_pause(self) -> bool {
+        // 标准输入为管道、重定向或已关闭时, 等待输入可能永远阻塞, 也没有人能按回车
+        if !io::stdin().is_terminal() {
+            return false;
+        }
+        match self {
+            PauseMode::Auto => owns_console(),
+            PauseMode::Always => true,
+            PauseMode::Never => false,
+        }
+    }
+}
+
+/// 当前进程是否独占控制台窗口, 即由资源管理器而非终端启动
+#[cfg(windows)]
+fn owns_console() -> bool {
+    #[link(name = "kernel32")]
+    unsafe extern "system" {
+        fn GetConsoleProcessList(process_list: *mut u32, process_count: u32) -> u32;
+    }
+
+    let mut processes = [0u32; 2];
+    // SAFETY: 传入的数量与缓冲区长度一致
+    let count = unsafe { GetConsoleProcessList(processes.as_mut_ptr(), processes.len() as u32) };
+    count == 1
 }
 
-fn replace_id_and_title(content: &str, new_number: &str) -> Option<String> {
-    let (id_start_tag, id_end_tag) = ("<id>", "</id>");
-    let (title_start_tag, title_end_tag) = (r##"<title locale="zh_CN"><![CDATA["##, "]]></title>");
+/// 其他平台无法可靠地判断启动方式, 由启动器传入 `--pause`
+#[cfg(not(windows))]
+fn owns_console() -> bool {
+    false
+}
 
-    replace_tag(content, id_start_tag, id_end_tag, new_number)
-        .and_then(|c| replace_tag(&c, title_start_tag, title_end_tag, new_number))
+pub fn pause_before_exit(mode: PauseMode) {
+    if !mode.should_pause() {
+        return;
+    }
+    eprint!("按回车键退出...");
+    let _ = io::stderr().flush();
+    let _ = io::stdin().read_line(&mut String::new());
 }
 
-fn replace_tag(content: &str, start_tag: &str, end_tag: &str, new_value: &str) -> Option<String> {
-    let start_index = content.find(start_tag)?;
-    let end_index_rel = content[start_index..].find(end_tag)?;
-    let end_index = start_index + end_index_rel + end_tag.len();
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn reproducible_id_depends_on_timestamp_and_package() {
+        let id = generate_lockscreen_number_at(1_700_000_000, "spring");
+        assert_eq!(id, generate_lockscreen_number_at(1_700_000_000, "spring"));
+        assert!(id.starts_with("20231114"), "{id}");
+        assert_eq!(id.len(), 11);
+
+        let ids: HashSet<String> = ["spring", "summer", "autumn", "winter"]
+            .iter()
+            .map(|package| generate_lockscreen_number_at(1_700_000_000, package))
+            .collect();
+        assert_eq!(ids.len(), 4);
+    }
 
-    let old_segment = &content[start_index..end_index];
-    let new_segment = format!("{start_tag}{new_value}{end_tag}");
+    #[test]
+    fn validate_id_accepts_single_safe_component() {
+        for id in ["20240101123", "my_lock-screen", "A"] {
+            assert!(validate_id(id).is_ok(), "{id}");
+        }
+    }
 
-    Some(content.replacen(old_segment, &new_segment, 1))
+    #[test]
+    fn validate_id_rejects_path_traversal() {
+        for id in [
+            "",
+            ".",
+            "..",
+            "../evil",
+            "a/b",
+            "a\\b",
+            "/tmp/evil",
+            "C:evil",
+            "id.zip",
+            "编号",
+            "a b",
+        ] {
+            let err = validate_id(id).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{id}");
+        }
+    }
 }
@@ -0,0 +1,87 @@
+use crate::batch::PackJob;
+use console::Emoji;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::io;
+use std::path::Path;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::Duration;
+use vivo_lockscreen_packing::{error::ArchiveError, pipeline::resolve_lockscreen_id};
+
+static ERROR_EMOJI: Emoji<'_, '_> = Emoji("❌ ", "ERR");
+static EYES: Emoji<'_, '_> = Emoji("👀 ", "");
+
+/// 最后一次文件变化后等待的时间, 期间的连续变化只触发一次打包
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 参与打包的路径, 其余文件（如输出文件、编辑器临时文件）的变化不触发打包
+const WATCHED_ENTRIES: [&str; 4] = ["lockscreen", "preview", "description.xml", ".vlpignore"];
+
+/// 监听锁屏包目录, 文件变化时重新打包, 直到进程被终止
+///
+/// 整个会话使用同一个锁屏编号, 第一次打包成功后写入 vlp.lock 与编号登记簿; 校验或打包失败时输出错误并继续监听。
+pub fn watch_lockscreen_package(mut job: PackJob) -> Result<(), ArchiveError> {
+    let resolved = resolve_lockscreen_id(&job.input, &job.output_dir, &job.options)?;
+    let options = job.options.clone();
+    job.options.id = Some(resolved.id.clone());
+    let mut pending = Some(resolved);
+    let input = job.input.as_path();
+
+    let root = input.canonicalize()?;
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(io::Error::other)?;
+    watcher
+        .watch(&root, RecursiveMode::Recursive)
+        .map_err(io::Error::other)?;
+
+    let mut build = || {
+        let result = job.run().and_then(|_| match pending.take() {
+            Some(resolved) => resolved.commit(input, &options),
+            None => Ok(()),
+        });
+        if let Err(e) = result {
+            eprintln!("{ERROR_EMOJI}打包失败, {e}");
+        }
+        println!("{EYES}正在监听 {} 的变化, 按 Ctrl+C 退出", input.display());
+    };
+
+    build();
+    loop {
+        let event = rx.recv().map_err(io::Error::other)?;
+        if !is_relevant(&root, event) {
+            continue;
+        }
+
+        // 等待变化停止后再打包
+        loop {
+            match rx.recv_timeout(DEBOUNCE) {
+                Ok(_) => continue,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::other("文件监听已中断").into());
+                }
+            }
+        }
+
+        build();
+    }
+}
+
+fn is_relevant(root: &Path, event: notify::Result<Event>) -> bool {
+    let Ok(event) = event else {
+        return false;
+    };
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+
+    event.paths.iter().any(|path| {
+        path.strip_prefix(root)
+            .ok()
+            .and_then(|relative| relative.components().next())
+            .is_some_and(|first| {
+                WATCHED_ENTRIES
+                    .iter()
+                    .any(|entry| first.as_os_str() == *entry)
+            })
+    })
+}
@@ -1,6 +1,13 @@
-use std::io;
+use std::fs;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Output, Stdio};
+
+use walkdir::WalkDir;
+
+use crate::archiver::{ArchiveOptions, Archiver, entry_name};
+use crate::compression::CompressionPolicy;
+use crate::error::ArchiveError;
 
 pub struct ZipCommand {
     pub path: PathBuf,
@@ -23,25 +30,25 @@ impl ZipCommand {
     /// Returns a `ZipCommand` instance with the resolved path on success, or an `io::Error` if not found.
     pub fn resolve(zip_command_path: Option<&Path>) -> io::Result<Self> {
         // customize zip path
-        if let Some(path) = zip_command_path {
-            if path.exists() {
-                return Ok(Self {
-                    path: path.to_path_buf(),
-                });
-            }
+        if let Some(path) = zip_command_path
+            && path.exists()
+        {
+            return Ok(Self {
+                path: path.to_path_buf(),
+            });
         }
 
         // find zip in exec program's parent path
-        if let Ok(exec_path) = std::env::current_exe() {
-            if let Some(parent) = exec_path.parent() {
-                #[cfg(windows)]
-                let candidate = parent.join("zip.exe");
-                #[cfg(not(windows))]
-                let candidate = parent.join("zip");
-
-                if candidate.exists() {
-                    return Ok(Self { path: candidate });
-                }
+        if let Ok(exec_path) = std::env::current_exe()
+            && let Some(parent) = exec_path.parent()
+        {
+            #[cfg(windows)]
+            let candidate = parent.join("zip.exe");
+            #[cfg(not(windows))]
+            let candidate = parent.join("zip");
+
+            if candidate.exists() {
+                return Ok(Self { path: candidate });
             }
         }
 
@@ -56,43 +63,130 @@ impl ZipCommand {
         ))
     }
 
-    pub fn zip_dir(&self, to: &Path, from: &Path) -> io::Result<()> {
-        let output = Command::new(&self.path)
-            .arg("-r")
+    /// zip 程序以非零状态退出时, 返回包含退出状态、stdout 与 stderr 的错误
+    pub fn report_error(&self, to: &Path, output: &Output) -> Result<(), ArchiveError> {
+        if !output.status.success() {
+            return Err(ArchiveError::ZipProcess {
+                stage: None,
+                path: to.to_path_buf(),
+                program: self.path.clone(),
+                status: output.status,
+                stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            });
+        }
+        Ok(())
+    }
+
+    fn spawn_error(&self, e: io::Error) -> ArchiveError {
+        ArchiveError::io_at(&self.path, e)
+    }
+
+    /// 压缩策略对应的 zip 参数: `-<级别>` 与仅存储的扩展名 `-n .png:.jpg:...`
+    fn compression_args(compression: &CompressionPolicy) -> Vec<String> {
+        let suffixes = compression
+            .stored_extensions()
+            .map(|ext| format!(".{ext}"))
+            .collect::<Vec<_>>()
+            .join(":");
+        vec![
+            format!("-{}", compression.level()),
+            "-n".to_string(),
+            suffixes,
+        ]
+    }
+}
+
+impl Archiver for ZipCommand {
+    fn zip_dir(
+        &self,
+        to: &Path,
+        from: &Path,
+        options: &ArchiveOptions,
+    ) -> Result<(), ArchiveError> {
+        // 由程序遍历目录并应用排除规则, 再通过 `-@` 将文件列表传给 zip
+        let mut names = String::new();
+        let walker = WalkDir::new(from)
+            .min_depth(1)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| {
+                !options
+                    .excludes
+                    .is_excluded(entry.path(), entry.file_type().is_dir())
+            });
+        let mut total_bytes = 0;
+        for entry in walker {
+            let entry = entry?;
+            let relative = entry
+                .path()
+                .strip_prefix(from)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            names.push_str(&entry_name(relative));
+            names.push('\n');
+            if entry.file_type().is_file() {
+                total_bytes += entry.metadata()?.len();
+            }
+        }
+
+        let mut child = Command::new(&self.path)
+            .args(Self::compression_args(&options.compression))
             .arg(to)
-            .arg(".")
+            .arg("-@")
             .current_dir(from)
-            .output()?;
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| self.spawn_error(e))?;
+        // zip 提前退出时写入会失败, 此时以退出状态与输出为准
+        if let Some(mut stdin) = child.stdin.take()
+            && let Err(e) = stdin.write_all(names.as_bytes())
+            && e.kind() != io::ErrorKind::BrokenPipe
+        {
+            return Err(e.into());
+        }
+        let output = child.wait_with_output()?;
 
-        self.report_error(&output)
+        self.report_error(to, &output)?;
+        // 外部程序无法报告中间进度, 完成后一次性推进
+        if let Some(progress) = &options.progress {
+            progress.advance(total_bytes);
+        }
+        Ok(())
     }
-    pub fn zip_file(&self, to: &Path, from: &Path) -> io::Result<()> {
+
+    fn zip_file(
+        &self,
+        to: &Path,
+        from: &Path,
+        options: &ArchiveOptions,
+    ) -> Result<(), ArchiveError> {
         let mut cmd = Command::new(&self.path);
 
         if let Some(file_name) = from.file_name()
             && let Some(parent_dir) = from.parent()
         {
             let output = cmd
+                .args(Self::compression_args(&options.compression))
                 .arg(to)
                 .arg(file_name)
                 .current_dir(parent_dir)
-                .output()?;
-            self.report_error(&output)
+                .output()
+                .map_err(|e| self.spawn_error(e))?;
+            self.report_error(to, &output)?;
+            if let Some(progress) = &options.progress {
+                progress.advance(fs::metadata(from)?.len());
+            }
+            Ok(())
         } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidInput,
+            Err(ArchiveError::InvalidPath(io::Error::new(
+                io::ErrorKind::InvalidInput,
                 format!(
                     "无效的文件路径, 无法获取文件名或父目录: [{}]",
                     from.display()
                 ),
-            ))
+            )))
         }
     }
-    pub fn report_error(&self, output: &std::process::Output) -> io::Result<()> {
-        if !output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            return Err(io::Error::other(format!("zip 命令执行失败: \n{stdout}")));
-        }
-        Ok(())
-    }
 }
@@ -0,0 +1,44 @@
+//! 集成测试共用的锁屏包样例与 vlp 调用
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// 1x1 PNG
+pub const PREVIEW_PNG: [u8; 69] = [
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x90, 0x77, 0x53,
+    0xde, 0x00, 0x00, 0x00, 0x0c, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0xf8, 0xff, 0xff, 0x3f,
+    0x00, 0x05, 0xfe, 0x02, 0xfe, 0x0d, 0xef, 0x46, 0xb8, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e,
+    0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+const DESCRIPTION_XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<theme>
+    <id>0</id>
+    <title locale="zh_CN"><![CDATA[测试锁屏]]></title>
+    <author>vlp</author>
+    <version>1.0</version>
+</theme>
+"#;
+
+/// 在 `root` 下写入一个可以通过检查的最小锁屏包
+pub fn write_package(root: &Path) {
+    fs::create_dir_all(root.join("preview")).unwrap();
+    fs::create_dir_all(root.join("lockscreen/images")).unwrap();
+    fs::write(root.join("description.xml"), DESCRIPTION_XML).unwrap();
+    fs::write(root.join("lockscreen/manifest.xml"), "<Lockscreen/>\n").unwrap();
+    fs::write(root.join("lockscreen/images/bg.png"), PREVIEW_PNG).unwrap();
+    fs::write(root.join("preview/preview_0.png"), PREVIEW_PNG).unwrap();
+}
+
+/// 以固定的 SOURCE_DATE_EPOCH 运行 vlp, 失败时 panic
+pub fn vlp(args: &[&Path]) {
+    let status = Command::new(env!("CARGO_BIN_EXE_vlp"))
+        .args(args)
+        .env("SOURCE_DATE_EPOCH", "1700000000")
+        .stdin(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success(), "vlp {args:?} failed: {status}");
+}
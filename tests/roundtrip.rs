@@ -0,0 +1,75 @@
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::{PREVIEW_PNG, vlp, write_package};
+use tempfile::TempDir;
+
+/// 用 `pack_args` 打包, 解包后不带任何参数重新打包, 两次输出应逐字节相同
+///
+/// 返回解包得到的 description.xml。
+fn assert_round_trip(pack_args: &[&Path]) -> String {
+    let tmp = TempDir::new().unwrap();
+    let source = tmp.path().join("source");
+    let first = tmp.path().join("first");
+    let unpacked = tmp.path().join("unpacked");
+    let second = tmp.path().join("second");
+    for dir in [&first, &unpacked, &second] {
+        fs::create_dir_all(dir).unwrap();
+    }
+    write_package(&source);
+
+    vlp(&[&[source.as_path(), Path::new("-o"), &first], pack_args].concat());
+    vlp(&[
+        Path::new("unpack"),
+        &first.join("lockscreen"),
+        Path::new("-o"),
+        &unpacked,
+    ]);
+
+    let restored = fs::read_dir(&unpacked)
+        .unwrap()
+        .next()
+        .unwrap()
+        .unwrap()
+        .path();
+    for entry in [
+        "preview/preview_0.png",
+        "lockscreen/manifest.xml",
+        "vlp.lock",
+        "vlp.toml",
+    ] {
+        assert!(restored.join(entry).is_file(), "missing {entry}");
+    }
+    assert_eq!(
+        fs::read(restored.join("lockscreen/images/bg.png")).unwrap(),
+        PREVIEW_PNG
+    );
+
+    vlp(&[&restored, Path::new("-o"), &second]);
+
+    assert_eq!(
+        fs::read(first.join("lockscreen")).unwrap(),
+        fs::read(second.join("lockscreen")).unwrap()
+    );
+    fs::read_to_string(restored.join("description.xml")).unwrap()
+}
+
+#[test]
+fn pack_unpack_pack_is_byte_identical() {
+    assert_round_trip(&[]);
+}
+
+#[test]
+fn non_default_title_survives_round_trip() {
+    let description = assert_round_trip(&[Path::new("--title-strategy"), Path::new("keep")]);
+    assert!(
+        description.contains("<![CDATA[测试锁屏]]>"),
+        "{description}"
+    );
+
+    let description =
+        assert_round_trip(&[Path::new("--title-strategy"), Path::new("{title}-{id}")]);
+    assert!(description.contains("<![CDATA[测试锁屏-"), "{description}");
+}